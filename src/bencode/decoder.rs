@@ -0,0 +1,385 @@
+//! Hand-rolled bencode decoder
+//!
+//! Unlike going through serde, decoding by hand lets us remember where each
+//! value sits in the input, which is what info-hash calculation needs. It
+//! also lets errors say at which byte the input went wrong.
+
+use super::Value;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::ops::Range;
+
+/// Nesting limit, so hostile input can't overflow the stack
+const MAX_DEPTH: usize = 256;
+
+/// Longest integer or string length prefix accepted from a stream: enough
+/// for any `i64` with its sign
+const MAX_NUMBER_LEN: usize = 20;
+
+/// Decode the bencode value at the start of `data`, remembering the byte
+/// range of the value stored under `key` in the top-level dictionary (if
+/// there is one)
+///
+/// Also returns where the value ends; any bytes after it are left for the
+/// caller to accept or reject.
+pub(crate) fn decode_with_key_span(
+    data: &[u8],
+    key: &str,
+) -> crate::Result<(Value, Option<Range<usize>>, usize)> {
+    let mut decoder = Decoder {
+        data,
+        pos: 0,
+        span_key: Some(key.as_bytes()),
+        span: None,
+    };
+    let value = decoder.value(0)?;
+    Ok((value, decoder.span, decoder.pos))
+}
+
+/// Decode a complete bencode value
+pub(crate) fn decode(data: &[u8]) -> crate::Result<Value> {
+    let mut decoder = Decoder {
+        data,
+        pos: 0,
+        span_key: None,
+        span: None,
+    };
+    let value = decoder.value(0)?;
+    if decoder.pos != data.len() {
+        return Err(decode_error("trailing data after value", decoder.pos));
+    }
+    Ok(value)
+}
+
+/// Decode one bencode value from `reader`, reading no further than its end
+///
+/// Reads a byte at a time, so wrap slow unbuffered readers in a
+/// `BufReader` (whose own position then stays correct).
+pub(crate) fn decode_from<R: Read>(reader: R) -> crate::Result<Value> {
+    StreamDecoder { reader, pos: 0 }.value(0)
+}
+
+fn decode_error(message: &str, offset: usize) -> crate::Error {
+    crate::Error::BencodeDecode {
+        message: message.to_string(),
+        offset: Some(offset),
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    span_key: Option<&'a [u8]>,
+    span: Option<Range<usize>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn peek(&self) -> crate::Result<u8> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| decode_error("unexpected end of input", self.pos))
+    }
+
+    fn value(&mut self, depth: usize) -> crate::Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(decode_error("nesting too deep", self.pos));
+        }
+        match self.peek()? {
+            b'i' => {
+                let start = self.pos;
+                self.pos += 1;
+                let end = self.find(b'e')?;
+                let n = parse_int(&self.data[self.pos..end]).map_err(|e| decode_error(e, start))?;
+                self.pos = end + 1;
+                Ok(Value::Int(n))
+            }
+            b'l' => {
+                self.pos += 1;
+                let mut list = Vec::new();
+                while self.peek()? != b'e' {
+                    list.push(self.value(depth + 1)?);
+                }
+                self.pos += 1;
+                Ok(Value::List(list))
+            }
+            b'd' => {
+                self.pos += 1;
+                let mut dict = BTreeMap::new();
+                while self.peek()? != b'e' {
+                    let key_start = self.pos;
+                    let key = self.bytes()?;
+                    let start = self.pos;
+                    let value = self.value(depth + 1)?;
+                    if depth == 0 && self.span_key == Some(key) {
+                        self.span = Some(start..self.pos);
+                    }
+                    let key = String::from_utf8(key.to_vec()).map_err(|_| {
+                        decode_error("dictionary key is not valid UTF-8", key_start)
+                    })?;
+                    dict.insert(key, value);
+                }
+                self.pos += 1;
+                Ok(Value::Dict(dict))
+            }
+            b'0'..=b'9' => Ok(Value::Bytes(self.bytes()?.to_vec())),
+            _ => Err(decode_error("invalid value type", self.pos)),
+        }
+    }
+
+    /// Read a `<length>:<bytes>` string
+    fn bytes(&mut self) -> crate::Result<&'a [u8]> {
+        let colon = self.find(b':')?;
+        let len = std::str::from_utf8(&self.data[self.pos..colon])
+            .ok()
+            .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or_else(|| decode_error("invalid string length", self.pos))?;
+        let start = colon + 1;
+        let end = start
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| decode_error("string runs past end of input", self.pos))?;
+        self.pos = end;
+        Ok(&self.data[start..end])
+    }
+
+    fn find(&self, byte: u8) -> crate::Result<usize> {
+        self.data[self.pos..]
+            .iter()
+            .position(|&b| b == byte)
+            .map(|i| self.pos + i)
+            .ok_or_else(|| decode_error("unexpected end of input", self.data.len()))
+    }
+}
+
+/// Incremental counterpart of [`Decoder`] over any reader
+struct StreamDecoder<R> {
+    reader: R,
+    /// Bytes consumed so far, for error offsets
+    pos: usize,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    fn next(&mut self) -> crate::Result<u8> {
+        let mut byte = [0u8];
+        match self.reader.read_exact(&mut byte) {
+            Ok(()) => {
+                self.pos += 1;
+                Ok(byte[0])
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Err(decode_error("unexpected end of input", self.pos))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> crate::Result<Value> {
+        let first = self.next()?;
+        self.value_from(first, depth)
+    }
+
+    /// Decode the value whose first byte, `first`, was already read
+    fn value_from(&mut self, first: u8, depth: usize) -> crate::Result<Value> {
+        let start = self.pos - 1;
+        if depth > MAX_DEPTH {
+            return Err(decode_error("nesting too deep", start));
+        }
+        match first {
+            b'i' => {
+                let digits = self.until(b'e', None)?;
+                let n = parse_int(&digits).map_err(|e| decode_error(e, start))?;
+                Ok(Value::Int(n))
+            }
+            b'l' => {
+                let mut list = Vec::new();
+                loop {
+                    match self.next()? {
+                        b'e' => return Ok(Value::List(list)),
+                        byte => list.push(self.value_from(byte, depth + 1)?),
+                    }
+                }
+            }
+            b'd' => {
+                let mut dict = BTreeMap::new();
+                loop {
+                    let key_start = self.pos;
+                    let key = match self.next()? {
+                        b'e' => return Ok(Value::Dict(dict)),
+                        byte @ b'0'..=b'9' => self.bytes(byte)?,
+                        _ => {
+                            return Err(decode_error("dictionary key is not a string", key_start));
+                        }
+                    };
+                    let key = String::from_utf8(key).map_err(|_| {
+                        decode_error("dictionary key is not valid UTF-8", key_start)
+                    })?;
+                    let value = self.value(depth + 1)?;
+                    dict.insert(key, value);
+                }
+            }
+            b'0'..=b'9' => Ok(Value::Bytes(self.bytes(first)?)),
+            _ => Err(decode_error("invalid value type", start)),
+        }
+    }
+
+    /// Read the rest of a `<length>:<bytes>` string whose first length digit
+    /// was already read
+    fn bytes(&mut self, first: u8) -> crate::Result<Vec<u8>> {
+        let start = self.pos - 1;
+        let digits = self.until(b':', Some(first))?;
+        let len = std::str::from_utf8(&digits)
+            .ok()
+            .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or_else(|| decode_error("invalid string length", start))?;
+
+        // Grow as data arrives rather than trusting the declared length
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut bytes)?;
+        self.pos += bytes.len();
+        if bytes.len() as u64 != len {
+            return Err(decode_error("string runs past end of input", start));
+        }
+        Ok(bytes)
+    }
+
+    /// Bytes up to (not including) `end`, starting with `first` if given
+    fn until(&mut self, end: u8, first: Option<u8>) -> crate::Result<Vec<u8>> {
+        let mut out: Vec<u8> = first.into_iter().collect();
+        loop {
+            match self.next()? {
+                byte if byte == end => return Ok(out),
+                _ if out.len() >= MAX_NUMBER_LEN => {
+                    return Err(decode_error("number too long", self.pos - 1));
+                }
+                byte => out.push(byte),
+            }
+        }
+    }
+}
+
+/// Parse the digits of an `i...e` integer, failing with just a message for
+/// the caller to place
+fn parse_int(digits: &[u8]) -> Result<i64, &'static str> {
+    let text = std::str::from_utf8(digits).map_err(|_| "invalid integer")?;
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    let canonical = !unsigned.is_empty()
+        && unsigned.bytes().all(|b| b.is_ascii_digit())
+        && (unsigned == "0" || !unsigned.starts_with('0'))
+        && text != "-0";
+    if !canonical {
+        return Err("invalid integer");
+    }
+    text.parse().map_err(|_| "integer out of range")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_span_points_at_value() {
+        let data = b"d1:ai1e4:infod1:xi2ee1:zi3ee";
+        let (_, span, _) = decode_with_key_span(data, "info").unwrap();
+        assert_eq!(&data[span.unwrap()], b"d1:xi2ee");
+
+        let mut trailing = data.to_vec();
+        trailing.extend_from_slice(b"junk");
+        let (_, _, end) = decode_with_key_span(&trailing, "info").unwrap();
+        assert_eq!(end, data.len());
+    }
+
+    #[test]
+    fn test_nested_key_is_not_matched() {
+        let data = b"d5:outerd4:infoi1eee";
+        let (_, span, _) = decode_with_key_span(data, "info").unwrap();
+        assert!(span.is_none());
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        for data in [
+            &b"i03e"[..],
+            b"i-0e",
+            b"ie",
+            b"5:abc",
+            b"l4:spam",
+            b"i1ei2e",
+            b"x",
+        ] {
+            assert!(decode(data).is_err(), "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn test_errors_report_offset() {
+        let offset = |result: crate::Result<Value>| match result {
+            Err(crate::Error::BencodeDecode { offset, .. }) => offset,
+            other => panic!("expected a decode error, got {:?}", other),
+        };
+
+        for (data, expected) in [
+            (&b"d3:fooi03ee"[..], 6),
+            (b"l4:spamxe", 7),
+            (b"d3:foo9:bare", 6),
+            (b"l4:spam", 7),
+            (b"i1ei2e", 3),
+        ] {
+            assert_eq!(offset(decode(data)), Some(expected), "{:?}", data);
+        }
+
+        for (data, expected) in [
+            (&b"d3:fooi03ee"[..], 6),
+            (b"l4:spamxe", 7),
+            (b"d3:foo9:bare", 6),
+            (b"di1ei2ee", 1),
+        ] {
+            assert_eq!(offset(decode_from(data)), Some(expected), "{:?}", data);
+        }
+
+        let message = decode(b"l4:spamxe").unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "Bencode decode error at byte 7: invalid value type"
+        );
+    }
+
+    #[test]
+    fn test_decode_from_stops_after_value() {
+        let data = b"d3:bar4:spam3:fooi42eerest";
+        let mut cursor = std::io::Cursor::new(&data[..]);
+
+        let value = decode_from(&mut cursor).unwrap();
+        assert_eq!(value, decode(&data[..data.len() - 4]).unwrap());
+        assert_eq!(cursor.position(), data.len() as u64 - 4);
+
+        let mut rest = Vec::new();
+        cursor.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"rest");
+    }
+
+    #[test]
+    fn test_decode_from_rejects_malformed_input() {
+        for data in [
+            &b"i03e"[..],
+            b"ie",
+            b"5:abc",
+            b"l4:spam",
+            b"di1ei2ee",
+            b"99999999999999999999999:x",
+            b"x",
+        ] {
+            assert!(decode_from(data).is_err(), "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn test_rejects_deep_nesting() {
+        let mut data = vec![b'l'; MAX_DEPTH + 2];
+        data.extend(vec![b'e'; MAX_DEPTH + 2]);
+        assert!(decode(&data).is_err());
+        assert!(decode_from(&data[..]).is_err());
+    }
+}
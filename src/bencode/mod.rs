@@ -7,6 +7,11 @@
 //! - Lists
 //! - Dictionaries
 
+mod decoder;
+mod pretty;
+
+pub(crate) use decoder::decode_with_key_span;
+
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -23,13 +28,64 @@ pub enum Value {
 impl Value {
     /// Decode bencode data into a Value
     pub fn decode(data: &[u8]) -> crate::Result<Self> {
-        serde_bencode::from_bytes(data).map_err(|e| crate::Error::BencodeDecode(e.to_string()))
+        decoder::decode(data)
+    }
+
+    /// Decode one value from a reader without buffering the whole input
+    ///
+    /// Reading stops right after the value, so the reader can be used for
+    /// whatever follows it.
+    pub fn decode_from<R: std::io::Read>(reader: R) -> crate::Result<Self> {
+        decoder::decode_from(reader)
     }
 
     /// Encode a Value into bencode format
     pub fn encode(&self) -> crate::Result<Vec<u8>> {
         serde_bencode::to_bytes(self).map_err(|e| crate::Error::BencodeEncode(e.to_string()))
     }
+
+    /// The dictionary, if this is one
+    pub fn as_dict(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// The integer, if this is one
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The raw byte string, if this is one
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// The list items, if this is a list
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Value under `key`, or `None` if missing or this is not a dictionary
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_dict()?.get(key)
+    }
+
+    /// Byte string under `key` as text, or `None` if missing, not a byte
+    /// string or not valid UTF-8
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        std::str::from_utf8(self.get(key)?.as_bytes()?).ok()
+    }
 }
 
 #[cfg(test)]
@@ -63,6 +119,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_accessors() {
+        let value = Value::decode(b"d3:bin2:\xff\xfe3:inti7e4:listli1ee4:name4:spame").unwrap();
+
+        assert_eq!(value.get_str("name"), Some("spam"));
+        assert_eq!(value.get("int").and_then(Value::as_int), Some(7));
+        assert_eq!(
+            value.get("list").and_then(Value::as_list),
+            Some(&[Value::Int(1)][..])
+        );
+        assert_eq!(
+            value.get("bin").and_then(Value::as_bytes),
+            Some(&[0xff, 0xfe][..])
+        );
+        assert_eq!(value.as_dict().map(BTreeMap::len), Some(4));
+
+        // Missing keys, wrong types and invalid UTF-8 all give None
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(value.get_str("bin"), None);
+        assert_eq!(value.get_str("int"), None);
+        assert_eq!(value.as_int(), None);
+        assert_eq!(value.as_bytes(), None);
+        assert_eq!(value.as_list(), None);
+        assert_eq!(Value::Int(1).as_dict(), None);
+        assert_eq!(Value::Int(1).get("name"), None);
+        assert_eq!(Value::List(Vec::new()).get("name"), None);
+    }
+
     #[test]
     fn test_encode_decode_roundtrip() {
         let original = Value::Int(123);
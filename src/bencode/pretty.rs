@@ -0,0 +1,127 @@
+//! Human-readable rendering of bencode values
+//!
+//! The derived `Debug` prints byte strings as arrays of numbers, which is
+//! unreadable for anything torrent-sized. This renders a JSON-like view
+//! instead, meant for people rather than parsers.
+
+use super::Value;
+use std::fmt::Write;
+
+/// Binary strings longer than this are cut short, since piece hashes can
+/// run to megabytes
+const MAX_BINARY_PREVIEW: usize = 32;
+
+/// Spaces per nesting level
+const INDENT: usize = 2;
+
+impl Value {
+    /// Render as indented JSON-like text for debugging
+    ///
+    /// Byte strings that are valid UTF-8 (without control characters) are
+    /// shown as quoted text; others as `<hex ...>`, truncated after 32
+    /// bytes with the full length noted.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, depth: usize) {
+        let pad = |depth: usize| " ".repeat(depth * INDENT);
+        match self {
+            Value::Int(n) => {
+                let _ = write!(out, "{}", n);
+            }
+            Value::Bytes(bytes) => write_bytes(out, bytes),
+            Value::List(list) if list.is_empty() => out.push_str("[]"),
+            Value::List(list) => {
+                out.push_str("[\n");
+                for (i, item) in list.iter().enumerate() {
+                    out.push_str(&pad(depth + 1));
+                    item.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < list.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(depth));
+                out.push(']');
+            }
+            Value::Dict(dict) if dict.is_empty() => out.push_str("{}"),
+            Value::Dict(dict) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in dict.iter().enumerate() {
+                    let _ = write!(out, "{}{:?}: ", pad(depth + 1), key);
+                    value.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < dict.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(depth));
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_bytes(out: &mut String, bytes: &[u8]) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => {
+            let _ = write!(out, "{:?}", text);
+        }
+        _ if bytes.len() > MAX_BINARY_PREVIEW => {
+            let _ = write!(
+                out,
+                "<hex {}… ({} bytes)>",
+                hex::encode(&bytes[..MAX_BINARY_PREVIEW]),
+                bytes.len()
+            );
+        }
+        _ => {
+            let _ = write!(out, "<hex {}>", hex::encode(bytes));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_torrent() {
+        let mut data =
+            b"d8:announce9:localhost4:infod6:lengthi1000e4:name4:test6:pieces40:".to_vec();
+        data.extend((0..40).map(|i| i as u8));
+        data.extend_from_slice(b"e5:emptyde4:listlee");
+        let value = Value::decode(&data).unwrap();
+
+        assert_eq!(
+            value.to_pretty_string(),
+            format!(
+                "{{\n  \
+                 \"announce\": \"localhost\",\n  \
+                 \"empty\": {{}},\n  \
+                 \"info\": {{\n    \
+                 \"length\": 1000,\n    \
+                 \"name\": \"test\",\n    \
+                 \"pieces\": <hex {}… (40 bytes)>\n  \
+                 }},\n  \
+                 \"list\": []\n\
+                 }}",
+                hex::encode((0..32).collect::<Vec<u8>>())
+            )
+        );
+    }
+
+    #[test]
+    fn test_pretty_scalars() {
+        assert_eq!(Value::Int(-3).to_pretty_string(), "-3");
+        assert_eq!(
+            Value::Bytes(b"say \"hi\"".to_vec()).to_pretty_string(),
+            "\"say \\\"hi\\\"\""
+        );
+        assert_eq!(
+            Value::Bytes(vec![0xff, 0x00]).to_pretty_string(),
+            "<hex ff00>"
+        );
+        assert_eq!(
+            Value::List(vec![Value::Int(1), Value::Int(2)]).to_pretty_string(),
+            "[\n  1,\n  2\n]"
+        );
+    }
+}
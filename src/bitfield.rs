@@ -0,0 +1,224 @@
+//! Piece availability bitfields
+//!
+//! Bits are stored most significant first, so piece 0 is the high bit of the
+//! first byte, matching the wire format of the `bitfield` message.
+
+use std::fs;
+use std::path::Path;
+
+/// First bytes of a piece cache file written by [`Bitfield::serialize_to`]
+const CACHE_MAGIC: &[u8; 4] = b"TCBF";
+
+/// Magic plus the big-endian `u64` piece count
+const CACHE_HEADER_LEN: usize = 12;
+
+/// One bit per piece, set when the piece is present
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitfield {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bitfield {
+    /// A bitfield for `len` pieces with no bits set
+    pub fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    /// Wrap packed bytes already checked to hold exactly `len` bits with the
+    /// spare bits clear
+    pub(crate) fn from_raw(bytes: Vec<u8>, len: usize) -> Self {
+        debug_assert_eq!(bytes.len(), len.div_ceil(8));
+        Self { bytes, len }
+    }
+
+    /// Number of pieces the bitfield covers
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the bitfield covers no pieces at all
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether piece `index` is present; out-of-range indices are not
+    pub fn has(&self, index: usize) -> bool {
+        index < self.len && self.bytes[index / 8] & mask(index) != 0
+    }
+
+    /// Mark piece `index` as present, ignoring out-of-range indices
+    pub fn set(&mut self, index: usize) {
+        if index < self.len {
+            self.bytes[index / 8] |= mask(index);
+        }
+    }
+
+    /// Number of pieces present
+    pub fn count(&self) -> usize {
+        self.bytes.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Whether every piece is present
+    pub fn is_complete(&self) -> bool {
+        self.count() == self.len
+    }
+
+    /// Indices present in `self` but not in `other`, in ascending order
+    ///
+    /// Pieces beyond the end of `other` count as absent from it.
+    pub fn difference(&self, other: &Bitfield) -> Vec<usize> {
+        (0..self.len)
+            .filter(|&i| self.has(i) && !other.has(i))
+            .collect()
+    }
+
+    /// Indices present in exactly one of the two bitfields, in ascending order
+    pub fn symmetric_difference(&self, other: &Bitfield) -> Vec<usize> {
+        (0..self.len.max(other.len))
+            .filter(|&i| self.has(i) != other.has(i))
+            .collect()
+    }
+
+    /// Packed bytes, as sent in a `bitfield` message
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Save to a piece cache file, so verified pieces needn't be rehashed
+    /// on the next start
+    ///
+    /// The file holds a magic number, the piece count and the packed bytes.
+    pub fn serialize_to<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let mut data = Vec::with_capacity(CACHE_HEADER_LEN + self.bytes.len());
+        data.extend_from_slice(CACHE_MAGIC);
+        data.extend_from_slice(&(self.len as u64).to_be_bytes());
+        data.extend_from_slice(&self.bytes);
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Load a piece cache written by [`Bitfield::serialize_to`]
+    ///
+    /// Fails with `Error::PieceCache` unless the file is well formed and
+    /// covers exactly `num_pieces` pieces, so a cache left over from another
+    /// torrent is never trusted.
+    pub fn deserialize_from<P: AsRef<Path>>(path: P, num_pieces: usize) -> crate::Result<Self> {
+        let data = fs::read(path)?;
+        let Some((header, bytes)) = data.split_first_chunk::<CACHE_HEADER_LEN>() else {
+            return Err(cache_error("file too short"));
+        };
+        if &header[..4] != CACHE_MAGIC {
+            return Err(cache_error("not a piece cache file"));
+        }
+
+        let count = u64::from_be_bytes(header[4..].try_into().unwrap());
+        if count != num_pieces as u64 {
+            return Err(cache_error(&format!(
+                "cache covers {} pieces, torrent has {}",
+                count, num_pieces
+            )));
+        }
+        if bytes.len() != num_pieces.div_ceil(8) {
+            return Err(cache_error("piece data has the wrong length"));
+        }
+
+        let spare = bytes.len() * 8 - num_pieces;
+        let spare_mask = ((1u16 << spare) - 1) as u8;
+        if bytes.last().is_some_and(|last| last & spare_mask != 0) {
+            return Err(cache_error("spare bits set after the last piece"));
+        }
+        Ok(Self::from_raw(bytes.to_vec(), num_pieces))
+    }
+}
+
+fn cache_error(message: &str) -> crate::Error {
+    crate::Error::PieceCache(message.to_string())
+}
+
+fn mask(index: usize) -> u8 {
+    0x80 >> (index % 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_has() {
+        let mut bitfield = Bitfield::new(10);
+        bitfield.set(0);
+        bitfield.set(9);
+        bitfield.set(10);
+
+        assert!(bitfield.has(0));
+        assert!(!bitfield.has(1));
+        assert!(bitfield.has(9));
+        assert!(!bitfield.has(10));
+        assert_eq!(bitfield.count(), 2);
+        assert!(!bitfield.is_complete());
+        assert_eq!(bitfield.as_bytes(), &[0x80, 0x40]);
+    }
+
+    fn with_bits(len: usize, bits: &[usize]) -> Bitfield {
+        let mut bitfield = Bitfield::new(len);
+        for &bit in bits {
+            bitfield.set(bit);
+        }
+        bitfield
+    }
+
+    #[test]
+    fn test_difference_overlapping() {
+        let resume = with_bits(12, &[0, 1, 2, 9]);
+        let disk = with_bits(12, &[1, 2, 3, 11]);
+
+        assert_eq!(resume.difference(&disk), vec![0, 9]);
+        assert_eq!(disk.difference(&resume), vec![3, 11]);
+        assert_eq!(resume.symmetric_difference(&disk), vec![0, 3, 9, 11]);
+        assert!(resume.difference(&resume).is_empty());
+    }
+
+    #[test]
+    fn test_difference_disjoint() {
+        let a = with_bits(8, &[0, 2, 4]);
+        let b = with_bits(10, &[1, 3, 9]);
+
+        assert_eq!(a.difference(&b), vec![0, 2, 4]);
+        assert_eq!(b.difference(&a), vec![1, 3, 9]);
+        assert_eq!(a.symmetric_difference(&b), vec![0, 1, 2, 3, 4, 9]);
+    }
+
+    #[test]
+    fn test_piece_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pieces.cache");
+        let bitfield = with_bits(13, &[0, 5, 12]);
+
+        bitfield.serialize_to(&path).unwrap();
+        assert_eq!(Bitfield::deserialize_from(&path, 13).unwrap(), bitfield);
+    }
+
+    #[test]
+    fn test_piece_cache_rejects_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pieces.cache");
+        with_bits(13, &[0]).serialize_to(&path).unwrap();
+
+        assert!(matches!(
+            Bitfield::deserialize_from(&path, 14),
+            Err(crate::Error::PieceCache(_))
+        ));
+
+        let mut data = fs::read(&path).unwrap();
+        data[0] = b'X';
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            Bitfield::deserialize_from(&path, 13),
+            Err(crate::Error::PieceCache(_))
+        ));
+    }
+}
@@ -5,8 +5,16 @@ pub enum Error {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("Bencode decode error: {0}")]
-    BencodeDecode(String),
+    /// Malformed bencode; `offset` is the byte where decoding failed, when
+    /// known
+    #[error(
+        "Bencode decode error{}: {message}",
+        offset.map(|o| format!(" at byte {}", o)).unwrap_or_default()
+    )]
+    BencodeDecode {
+        message: String,
+        offset: Option<usize>,
+    },
 
     #[error("Bencode encode error: {0}")]
     BencodeEncode(String),
@@ -17,6 +25,18 @@ pub enum Error {
     #[error("Tracker error: {0}")]
     Tracker(String),
 
+    #[error("Peer error: {0}")]
+    Peer(String),
+
+    #[error("Invalid handshake: {0}")]
+    Handshake(String),
+
+    #[error("Invalid piece cache: {0}")]
+    PieceCache(String),
+
+    #[error("Invalid magnet link: {0}")]
+    Magnet(String),
+
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
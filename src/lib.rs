@@ -1,9 +1,12 @@
 pub mod bencode;
+pub mod bitfield;
 pub mod error;
+pub mod magnet;
 pub mod metainfo;
 pub mod peer;
 pub mod tracker;
 
+pub use bitfield::Bitfield;
 pub use error::{Error, Result};
 pub use metainfo::Metainfo;
-pub use tracker::{Tracker, TrackerResponse};
+pub use tracker::{Tracker, TrackerManager, TrackerResponse, TrackerSession};
@@ -0,0 +1,256 @@
+//! Magnet URI parsing and generation
+//!
+//! A magnet link identifies a torrent by its info hash and can carry a
+//! display name and trackers. Hybrid torrents (BEP 52) list both their v1
+//! and v2 hashes as separate `xt` parameters.
+
+use crate::Metainfo;
+use std::fmt;
+use url::form_urlencoded;
+
+/// Hex multihash prefix for SHA2-256: function code 0x12, digest length 32
+const SHA256_MULTIHASH_PREFIX: &str = "1220";
+
+/// RFC 4648 base32 alphabet, used by older clients for v1 hashes
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Fields extracted from a magnet URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MagnetLink {
+    /// v1 info hash from `xt=urn:btih:`
+    pub info_hash_v1: Option<[u8; 20]>,
+    /// v2 info hash from `xt=urn:btmh:`, without the multihash prefix
+    pub info_hash_v2: Option<[u8; 32]>,
+    /// Display name (`dn`)
+    pub display_name: Option<String>,
+    /// Tracker URLs (`tr`), in the order given
+    pub trackers: Vec<String>,
+}
+
+impl MagnetLink {
+    /// Parse a `magnet:?` URI
+    ///
+    /// Every `xt` naming a BitTorrent hash is validated; other `xt` URNs are
+    /// ignored. At least one v1 or v2 hash must be present.
+    pub fn parse(uri: &str) -> crate::Result<Self> {
+        let query = uri
+            .strip_prefix("magnet:?")
+            .ok_or_else(|| magnet_error("not a magnet URI"))?;
+
+        let mut magnet = MagnetLink {
+            info_hash_v1: None,
+            info_hash_v2: None,
+            display_name: None,
+            trackers: Vec::new(),
+        };
+
+        for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+            match name.as_ref() {
+                "xt" => magnet.add_exact_topic(&value)?,
+                "dn" => magnet.display_name = Some(value.into_owned()),
+                "tr" => magnet.trackers.push(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        if magnet.info_hash_v1.is_none() && magnet.info_hash_v2.is_none() {
+            return Err(magnet_error(
+                "no `xt=urn:btih:` or `xt=urn:btmh:` parameter",
+            ));
+        }
+        Ok(magnet)
+    }
+
+    fn add_exact_topic(&mut self, xt: &str) -> crate::Result<()> {
+        if let Some(hash) = xt.strip_prefix("urn:btih:") {
+            let decoded = match hash.len() {
+                40 => decode_hex::<20>(hash),
+                32 => decode_base32(hash),
+                len => {
+                    return Err(magnet_error(format!(
+                        "v1 info hash must be 40 hex or 32 base32 characters, got {}",
+                        len
+                    )));
+                }
+            };
+            let hash =
+                decoded.ok_or_else(|| magnet_error(format!("invalid v1 info hash `{}`", hash)))?;
+            set_once(&mut self.info_hash_v1, hash, "v1")
+        } else if let Some(multihash) = xt.strip_prefix("urn:btmh:") {
+            let hash = multihash
+                .strip_prefix(SHA256_MULTIHASH_PREFIX)
+                .and_then(decode_hex::<32>)
+                .ok_or_else(|| magnet_error(format!("invalid v2 info hash `{}`", multihash)))?;
+            set_once(&mut self.info_hash_v2, hash, "v2")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Magnet link for a parsed torrent, with its name and every tracker
+    pub fn from_metainfo(metainfo: &Metainfo) -> Self {
+        Self {
+            info_hash_v1: Some(metainfo.info_hash),
+            info_hash_v2: None,
+            display_name: Some(metainfo.info.name.clone()),
+            trackers: metainfo.all_trackers(),
+        }
+    }
+}
+
+impl fmt::Display for MagnetLink {
+    /// Format as a `magnet:?` URI, hashes first, v1 hashes in hex
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut params = Vec::new();
+        if let Some(hash) = &self.info_hash_v1 {
+            params.push(format!("xt=urn:btih:{}", hex::encode(hash)));
+        }
+        if let Some(hash) = &self.info_hash_v2 {
+            params.push(format!(
+                "xt=urn:btmh:{}{}",
+                SHA256_MULTIHASH_PREFIX,
+                hex::encode(hash)
+            ));
+        }
+        if let Some(name) = &self.display_name {
+            params.push(format!("dn={}", encode_value(name)));
+        }
+        for tracker in &self.trackers {
+            params.push(format!("tr={}", encode_value(tracker)));
+        }
+        write!(f, "magnet:?{}", params.join("&"))
+    }
+}
+
+impl Metainfo {
+    /// Magnet link for this torrent; see [`MagnetLink::from_metainfo`]
+    pub fn magnet_link(&self) -> MagnetLink {
+        MagnetLink::from_metainfo(self)
+    }
+}
+
+fn encode_value(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+/// Store `hash`, rejecting a different hash of the same version
+fn set_once<const N: usize>(
+    slot: &mut Option<[u8; N]>,
+    hash: [u8; N],
+    version: &str,
+) -> crate::Result<()> {
+    match slot {
+        Some(existing) if *existing != hash => {
+            Err(magnet_error(format!("conflicting {} info hashes", version)))
+        }
+        _ => {
+            *slot = Some(hash);
+            Ok(())
+        }
+    }
+}
+
+fn decode_hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    hex::decode(text).ok()?.try_into().ok()
+}
+
+/// Decode 32 unpadded base32 characters (either case) into a v1 hash
+fn decode_base32(text: &str) -> Option<[u8; 20]> {
+    let mut hash = [0u8; 20];
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    let mut out = 0;
+    for c in text.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            *hash.get_mut(out)? = (buffer >> bits) as u8;
+            out += 1;
+        }
+    }
+    (out == hash.len()).then_some(hash)
+}
+
+fn magnet_error(message: impl Into<String>) -> crate::Error {
+    crate::Error::Magnet(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &str = "c9e15763f722f23e98a29decdfae341b98d53056";
+    const V2: &str = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e";
+
+    #[test]
+    fn test_hybrid_magnet() {
+        let uri = format!(
+            "magnet:?xt=urn:btih:{}&xt=urn:btmh:1220{}&dn=Some+Name\
+             &tr=http%3A%2F%2Ftracker.test%2Fannounce",
+            V1, V2
+        );
+        let magnet = MagnetLink::parse(&uri).unwrap();
+
+        assert_eq!(magnet.info_hash_v1, Some(decode_hex(V1).unwrap()));
+        assert_eq!(magnet.info_hash_v2, Some(decode_hex(V2).unwrap()));
+        assert_eq!(magnet.display_name.as_deref(), Some("Some Name"));
+        assert_eq!(magnet.trackers, vec!["http://tracker.test/announce"]);
+    }
+
+    #[test]
+    fn test_base32_info_hash() {
+        // The same hash as V1, base32-encoded
+        let uri = "magnet:?xt=urn:btih:ZHQVOY7XELZD5GFCTXWN7LRUDOMNKMCW";
+        let magnet = MagnetLink::parse(uri).unwrap();
+        assert_eq!(magnet.info_hash_v1, Some(decode_hex(V1).unwrap()));
+
+        let lower = MagnetLink::parse(&uri.to_lowercase()).unwrap();
+        assert_eq!(lower.info_hash_v1, magnet.info_hash_v1);
+    }
+
+    #[test]
+    fn test_generate_from_metainfo() {
+        let torrent = "d8:announce25:http://tracker.test/a?k=113:announce-listl\
+                       l25:http://tracker.test/a?k=1el20:udp://backup.test:80ee\
+                       4:infod6:lengthi1000e4:name9:My File 112:piece lengthi512e\
+                       6:pieces40:1234567890123456789012345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        let uri = metainfo.magnet_link().to_string();
+        assert_eq!(
+            uri,
+            format!(
+                "magnet:?xt=urn:btih:{}&dn=My+File+1\
+                 &tr=http%3A%2F%2Ftracker.test%2Fa%3Fk%3D1&tr=udp%3A%2F%2Fbackup.test%3A80",
+                hex::encode(metainfo.info_hash)
+            )
+        );
+
+        let parsed = MagnetLink::parse(&uri).unwrap();
+        assert_eq!(parsed, MagnetLink::from_metainfo(&metainfo));
+    }
+
+    #[test]
+    fn test_invalid_exact_topics() {
+        for uri in [
+            "magnet:?dn=nothing".to_string(),
+            "http://example.com/?xt=urn:btih:".to_string(),
+            format!("magnet:?xt=urn:btih:{}", &V1[..39]),
+            // Right length for base32, but `1` isn't in its alphabet
+            "magnet:?xt=urn:btih:1HQVOY7XELZD5GFCTXW57LRUDOMNKMCW".to_string(),
+            // SHA-1 multihash code instead of SHA2-256
+            format!("magnet:?xt=urn:btmh:1114{}", &V2[..40]),
+            format!("magnet:?xt=urn:btih:{}&xt=urn:btih:{}", V1, &V2[..40]),
+        ] {
+            assert!(
+                matches!(MagnetLink::parse(&uri), Err(crate::Error::Magnet(_))),
+                "{}",
+                uri
+            );
+        }
+    }
+}
@@ -5,7 +5,6 @@
 use clap::Parser;
 use std::path::PathBuf;
 use torrent_crab::metainfo::FileInfo;
-use tracing_subscriber;
 
 #[derive(Parser)]
 #[command(name = "torrent-crab")]
@@ -30,7 +29,9 @@ fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
-    println!("BitTorrent Client - A demo showcasing parsing torrent files, tracker communication, and discovering peers\n");
+    println!(
+        "BitTorrent Client - A demo showcasing parsing torrent files, tracker communication, and discovering peers\n"
+    );
 
     // Parse .torrent file
     println!("Parsing torrent file: {}", cli.torrent.display());
@@ -79,17 +80,19 @@ fn main() -> anyhow::Result<()> {
     println!("\nOur Peer ID: {}", String::from_utf8_lossy(&peer_id[0..8]));
 
     // Contact tracker
+    let Some(announce) = metainfo.announce.clone() else {
+        println!("\nNo tracker: this torrent relies on DHT");
+        return Ok(());
+    };
     println!("\nContacting tracker...");
-    let tracker = torrent_crab::Tracker::new(metainfo.announce.clone());
-    let request = torrent_crab::tracker::TrackerRequest::new_started(
-        metainfo.info_hash,
-        peer_id,
-        cli.port,
-        metainfo.total_size(),
-    );
+    let tracker = torrent_crab::Tracker::new(announce);
+    let request = torrent_crab::tracker::TrackerRequest::for_torrent(&metainfo, peer_id, cli.port);
 
     match tracker.announce(&request) {
-        Ok(response) => {
+        Ok(mut response) => {
+            let resolved = response.resolve_peer_hosts();
+            response.peers.extend(resolved);
+
             println!("\nTracker Response:");
             println!("   Interval: {} seconds", response.interval);
 
@@ -0,0 +1,94 @@
+//! Rough content classification from file extensions
+//!
+//! Releases commonly ship small extras next to the main content (subtitles,
+//! cover art, `.nfo` files), so those extensions don't count towards the
+//! category.
+
+use super::Metainfo;
+
+/// Broad kind of content a torrent holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCategory {
+    Video,
+    Audio,
+    Archive,
+    Software,
+    Document,
+    /// Files of more than one category
+    Mixed,
+    /// No file has a recognized extension
+    Unknown,
+}
+
+impl Metainfo {
+    /// Guess the kind of content from the file extensions present
+    pub fn content_category(&self) -> ContentCategory {
+        let mut found = None;
+        for file in self.info.files() {
+            let Some(category) = file.path.last().and_then(|name| classify(name)) else {
+                continue;
+            };
+            match found {
+                None => found = Some(category),
+                Some(existing) if existing != category => return ContentCategory::Mixed,
+                Some(_) => {}
+            }
+        }
+        found.unwrap_or(ContentCategory::Unknown)
+    }
+}
+
+/// Category for a file name, or `None` for extras and unknown extensions
+fn classify(name: &str) -> Option<ContentCategory> {
+    let (_, extension) = name.rsplit_once('.')?;
+    let category = match extension.to_ascii_lowercase().as_str() {
+        "mkv" | "mp4" | "avi" | "mov" | "wmv" | "webm" | "m4v" | "mpg" | "mpeg" | "ts" => {
+            ContentCategory::Video
+        }
+        "mp3" | "flac" | "ogg" | "opus" | "wav" | "aac" | "m4a" | "ape" | "wma" => {
+            ContentCategory::Audio
+        }
+        "zip" | "rar" | "7z" | "tar" | "gz" | "bz2" | "xz" | "zst" => ContentCategory::Archive,
+        "exe" | "msi" | "dmg" | "pkg" | "deb" | "rpm" | "apk" | "appimage" | "iso" => {
+            ContentCategory::Software
+        }
+        "pdf" | "epub" | "mobi" | "azw3" | "djvu" | "doc" | "docx" | "odt" | "cbz" | "cbr" => {
+            ContentCategory::Document
+        }
+        _ => return None,
+    };
+    Some(category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent_with_files(files: &[&str]) -> Metainfo {
+        let entries: String = files
+            .iter()
+            .map(|name| format!("d6:lengthi100e4:pathl{}:{}ee", name.len(), name))
+            .collect();
+        let torrent = format!(
+            "d8:announce9:localhost4:infod5:filesl{}e4:name4:test\
+             12:piece lengthi16384e6:pieces20:12345678901234567890ee",
+            entries
+        );
+        Metainfo::from_bytes(torrent.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_movie_with_extras() {
+        let metainfo = torrent_with_files(&["Movie.2020.MKV", "Movie.2020.srt", "info.nfo"]);
+        assert_eq!(metainfo.content_category(), ContentCategory::Video);
+    }
+
+    #[test]
+    fn test_mixed_and_unknown() {
+        let metainfo = torrent_with_files(&["album.flac", "setup.exe", "cover.jpg"]);
+        assert_eq!(metainfo.content_category(), ContentCategory::Mixed);
+
+        let metainfo = torrent_with_files(&["README", "notes.txt"]);
+        assert_eq!(metainfo.content_category(), ContentCategory::Unknown);
+    }
+}
@@ -0,0 +1,624 @@
+//! Creating .torrent metadata from files on disk
+//!
+//! A single file produces a single-file torrent named after the file. A
+//! directory produces a multi-file torrent containing every regular file
+//! beneath it, ordered by path so the output is reproducible.
+//!
+//! Hashing huge content can be interrupted; [`create_from_path_resumable`]
+//! checkpoints the hashed pieces to a [`CreationProgress`] file so a restart
+//! picks up where it left off.
+
+use super::{FileEntry, FileInfo, Info, MAX_PIECE_LENGTH, Metainfo, hash_info};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Smallest piece length we create (16 KiB, the standard block size)
+const MIN_PIECE_LENGTH: u64 = 16 * 1024;
+
+/// Largest piece length picked automatically (16 MiB)
+const MAX_AUTO_PIECE_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// Number of pieces automatic selection aims for
+const TARGET_PIECE_COUNT: u64 = 1500;
+
+/// How many newly hashed pieces between progress checkpoints
+const CHECKPOINT_PIECES: usize = 64;
+
+/// Piece length to use when creating a torrent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PieceLength {
+    /// Pick a piece length from the content size (see [`recommended_piece_length`])
+    Auto,
+    /// Use exactly this many bytes per piece
+    ///
+    /// Must be a power-of-two multiple of 16 KiB, at most 64 MiB.
+    Fixed(u64),
+}
+
+impl PieceLength {
+    /// Resolve to a concrete piece length for content of the given size
+    fn resolve(self, total_size: u64) -> crate::Result<u64> {
+        match self {
+            PieceLength::Auto => Ok(recommended_piece_length(total_size)),
+            PieceLength::Fixed(length) => {
+                if !(MIN_PIECE_LENGTH..=MAX_PIECE_LENGTH).contains(&length)
+                    || !length.is_power_of_two()
+                {
+                    return Err(crate::Error::InvalidMetainfo(format!(
+                        "Piece length {} must be a power-of-two multiple of 16 KiB, at most 64 MiB",
+                        length
+                    )));
+                }
+                Ok(length)
+            }
+        }
+    }
+}
+
+/// Pieces hashed so far by an interrupted torrent creation
+///
+/// Only valid for the same content layout and piece length it was made
+/// with; anything else is detected and hashing starts over. A file can
+/// change without changing size, so each file's modification time is
+/// recorded too, and pieces from the first modified file onwards are hashed
+/// again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreationProgress {
+    piece_length: u64,
+    layout_hash: [u8; 20],
+    /// Modification time of each source file, in nanoseconds since the Unix
+    /// epoch
+    mtimes: Vec<u64>,
+    pieces: Vec<[u8; 20]>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct BencodeProgress {
+    #[serde(rename = "piece length")]
+    piece_length: u64,
+    #[serde(rename = "layout hash", with = "serde_bytes")]
+    layout_hash: Vec<u8>,
+    mtimes: Vec<u64>,
+    #[serde(with = "serde_bytes")]
+    pieces: Vec<u8>,
+}
+
+impl CreationProgress {
+    /// Number of pieces already hashed
+    pub fn pieces_hashed(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Write the progress to `path` as a small bencoded file
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        let encoded = serde_bencode::to_bytes(&BencodeProgress {
+            piece_length: self.piece_length,
+            layout_hash: self.layout_hash.to_vec(),
+            mtimes: self.mtimes.clone(),
+            pieces: self.pieces.concat(),
+        })
+        .map_err(|e| crate::Error::BencodeEncode(e.to_string()))?;
+        fs::write(path, encoded)?;
+        Ok(())
+    }
+
+    /// Read progress written by [`CreationProgress::save`]
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let progress: BencodeProgress =
+            serde_bencode::from_bytes(&fs::read(path)?).map_err(|e| {
+                crate::Error::BencodeDecode {
+                    message: e.to_string(),
+                    offset: None,
+                }
+            })?;
+        let layout_hash =
+            progress
+                .layout_hash
+                .try_into()
+                .map_err(|_| crate::Error::BencodeDecode {
+                    message: "invalid layout hash".to_string(),
+                    offset: None,
+                })?;
+        if !progress.pieces.len().is_multiple_of(20) {
+            return Err(crate::Error::BencodeDecode {
+                message: "Pieces length must be multiple of 20".to_string(),
+                offset: None,
+            });
+        }
+
+        Ok(Self {
+            piece_length: progress.piece_length,
+            layout_hash,
+            mtimes: progress.mtimes,
+            pieces: progress
+                .pieces
+                .chunks_exact(20)
+                .map(|c| c.try_into().unwrap())
+                .collect(),
+        })
+    }
+
+    /// How many recorded pieces are still valid for `info`, whose source
+    /// files now have modification times `mtimes`
+    ///
+    /// `None` for a different layout or piece length. Otherwise only the
+    /// pieces wholly before the first file whose mtime changed are kept.
+    fn valid_pieces(&self, info: &Info, mtimes: &[u64]) -> Option<usize> {
+        let matches = self.piece_length == info.piece_length
+            && self.layout_hash == info.layout_hash()
+            && self.pieces.len() <= info.total_size().div_ceil(info.piece_length) as usize;
+        if !matches {
+            return None;
+        }
+
+        let mut start = 0;
+        for (i, file) in info.files().iter().enumerate() {
+            if self.mtimes.get(i) != mtimes.get(i) {
+                let unchanged = (start / info.piece_length) as usize;
+                return Some(self.pieces.len().min(unchanged));
+            }
+            start += file.length;
+        }
+        Some(self.pieces.len())
+    }
+}
+
+/// Modification time of `path` in nanoseconds since the Unix epoch
+fn modified_nanos(path: &Path) -> crate::Result<u64> {
+    let modified = fs::metadata(path)?.modified()?;
+    let since_epoch = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(u64::try_from(since_epoch.as_nanos()).unwrap_or(u64::MAX))
+}
+
+/// Recommend a piece length for content of the given size
+///
+/// Aims for roughly 1500 pieces, rounded up to a power of two and clamped
+/// between 16 KiB and 16 MiB.
+pub fn recommended_piece_length(total_size: u64) -> u64 {
+    let ideal = total_size.div_ceil(TARGET_PIECE_COUNT);
+    ideal
+        .checked_next_power_of_two()
+        .unwrap_or(MAX_AUTO_PIECE_LENGTH)
+        .clamp(MIN_PIECE_LENGTH, MAX_AUTO_PIECE_LENGTH)
+}
+
+/// Create torrent metadata for a file or directory
+pub fn create_from_path<P: AsRef<Path>>(
+    path: P,
+    announce: &str,
+    piece_length: PieceLength,
+) -> crate::Result<Metainfo> {
+    let (sources, mut info) = prepare(path.as_ref(), piece_length)?;
+    info.pieces = hash_pieces(&sources, info.piece_length, Vec::new(), |_| Ok(()))?;
+    finish(info, announce)
+}
+
+/// Like [`create_from_path`], but checkpoint progress to `state_path`
+///
+/// If `state_path` holds progress from an earlier run over the same content
+/// and piece length, hashing resumes after the pieces it records. Progress
+/// is saved every few dozen pieces, and the file is removed once the torrent
+/// is complete.
+pub fn create_from_path_resumable<P: AsRef<Path>>(
+    path: P,
+    announce: &str,
+    piece_length: PieceLength,
+    state_path: &Path,
+) -> crate::Result<Metainfo> {
+    let (sources, mut info) = prepare(path.as_ref(), piece_length)?;
+    let mtimes = sources
+        .iter()
+        .map(|source| modified_nanos(source))
+        .collect::<crate::Result<Vec<_>>>()?;
+
+    let done = match CreationProgress::load(state_path) {
+        Ok(mut progress) => match progress.valid_pieces(&info, &mtimes) {
+            Some(valid) => {
+                if valid < progress.pieces_hashed() {
+                    tracing::debug!(
+                        "Rehashing {} pieces of files modified since the last run",
+                        progress.pieces_hashed() - valid
+                    );
+                }
+                tracing::debug!("Resuming creation after {} pieces", valid);
+                progress.pieces.truncate(valid);
+                progress.pieces
+            }
+            None => {
+                tracing::debug!("Ignoring progress saved for different content");
+                Vec::new()
+            }
+        },
+        Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    let layout_hash = info.layout_hash();
+    info.pieces = hash_pieces(&sources, info.piece_length, done, |pieces| {
+        CreationProgress {
+            piece_length: info.piece_length,
+            layout_hash,
+            mtimes: mtimes.clone(),
+            pieces: pieces.to_vec(),
+        }
+        .save(state_path)
+    })?;
+
+    let metainfo = finish(info, announce)?;
+    fs::remove_file(state_path).or_else(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Ok(()),
+        _ => Err(e),
+    })?;
+    Ok(metainfo)
+}
+
+/// Collect the files to hash and build the info dictionary without pieces
+fn prepare(path: &Path, piece_length: PieceLength) -> crate::Result<(Vec<PathBuf>, Info)> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            crate::Error::InvalidMetainfo(format!("Cannot name torrent for {}", path.display()))
+        })?;
+
+    let metadata = fs::metadata(path)?;
+    let (sources, files) = if metadata.is_dir() {
+        let mut sources = Vec::new();
+        collect_files(path, &mut sources)?;
+        sources.sort();
+
+        let mut entries = Vec::with_capacity(sources.len());
+        for source in &sources {
+            let relative = source.strip_prefix(path).unwrap_or(source);
+            entries.push(FileEntry {
+                path: relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect(),
+                length: fs::metadata(source)?.len(),
+                attributes: String::new(),
+            });
+        }
+        (sources, FileInfo::Multi { files: entries })
+    } else {
+        (
+            vec![path.to_path_buf()],
+            FileInfo::Single {
+                length: metadata.len(),
+            },
+        )
+    };
+
+    let mut info = Info {
+        name_bytes: name.clone().into_bytes(),
+        name,
+        piece_length: 0,
+        pieces: Vec::new(),
+        skipped_pieces: None,
+        files,
+        private: false,
+    };
+    let total_size = info.total_size();
+    if total_size == 0 {
+        return Err(crate::Error::InvalidMetainfo(
+            "Cannot create a torrent from empty content".to_string(),
+        ));
+    }
+
+    info.piece_length = piece_length.resolve(total_size)?;
+    Ok((sources, info))
+}
+
+/// Fill in the remaining metadata around a fully hashed info dictionary
+fn finish(info: Info, announce: &str) -> crate::Result<Metainfo> {
+    let info_hash = hash_info(&info.to_bencode())?;
+
+    let created_by = format!("torrent-crab/{}", env!("CARGO_PKG_VERSION"));
+    let creation_date = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .ok();
+
+    Ok(Metainfo {
+        announce: Some(announce.to_string()),
+        announce_list: Vec::new(),
+        info_hash,
+        info,
+        info_bytes: None,
+        creation_date,
+        comment: None,
+        comment_bytes: None,
+        created_by_bytes: Some(created_by.clone().into_bytes()),
+        created_by: Some(created_by),
+        nodes: Vec::new(),
+        nodes6: Vec::new(),
+        url_list: Vec::new(),
+        meta_version: None,
+    })
+}
+
+/// Recursively collect regular files below `dir`
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> crate::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), out)?;
+        } else if file_type.is_file() {
+            out.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Hash the concatenated contents of `sources` in pieces of `piece_length`
+///
+/// Hashing starts after the pieces already in `done`. `checkpoint` is given
+/// all pieces so far every [`CHECKPOINT_PIECES`] new pieces.
+fn hash_pieces(
+    sources: &[PathBuf],
+    piece_length: u64,
+    done: Vec<[u8; 20]>,
+    mut checkpoint: impl FnMut(&[[u8; 20]]) -> crate::Result<()>,
+) -> crate::Result<Vec<[u8; 20]>> {
+    let mut skip = done.len() as u64 * piece_length;
+    let mut pieces = done;
+    let mut since_checkpoint = 0;
+    let mut buffer = Vec::with_capacity(piece_length as usize);
+
+    for source in sources {
+        let mut file = File::open(source)?;
+        if skip > 0 {
+            let length = file.metadata()?.len();
+            if skip >= length {
+                skip -= length;
+                continue;
+            }
+            file.seek(SeekFrom::Start(skip))?;
+            skip = 0;
+        }
+        loop {
+            let want = piece_length - buffer.len() as u64;
+            let read = (&mut file).take(want).read_to_end(&mut buffer)?;
+            if buffer.len() as u64 == piece_length {
+                pieces.push(Sha1::digest(&buffer).into());
+                buffer.clear();
+                since_checkpoint += 1;
+                if since_checkpoint == CHECKPOINT_PIECES {
+                    checkpoint(&pieces)?;
+                    since_checkpoint = 0;
+                }
+            }
+            if read == 0 {
+                break;
+            }
+        }
+    }
+
+    if !buffer.is_empty() {
+        pieces.push(Sha1::digest(&buffer).into());
+    }
+
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_recommended_piece_length() {
+        assert_eq!(recommended_piece_length(1000), MIN_PIECE_LENGTH);
+        // 700 MB / 1500 ≈ 466 KiB, rounded up to 512 KiB
+        assert_eq!(recommended_piece_length(700 * 1024 * 1024), 512 * 1024);
+        assert_eq!(recommended_piece_length(u64::MAX), MAX_AUTO_PIECE_LENGTH);
+    }
+
+    #[test]
+    fn test_create_auto_piece_length() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, vec![7u8; 100_000]).unwrap();
+
+        let metainfo =
+            create_from_path(&path, "http://tracker.test/announce", PieceLength::Auto).unwrap();
+
+        assert_eq!(metainfo.info.name, "data.bin");
+        assert_eq!(
+            metainfo.info.piece_length,
+            recommended_piece_length(100_000)
+        );
+        assert_eq!(metainfo.total_size(), 100_000);
+        // 100_000 bytes in 16 KiB pieces
+        assert_eq!(metainfo.num_pieces(), 7);
+    }
+
+    #[test]
+    fn test_create_multi_file_hashes_across_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("album");
+        fs::create_dir_all(root.join("disc1")).unwrap();
+        fs::write(root.join("disc1").join("a.bin"), vec![1u8; 10_000]).unwrap();
+        fs::write(root.join("b.bin"), vec![2u8; 10_000]).unwrap();
+
+        let metainfo = create_from_path(
+            &root,
+            "http://tracker.test/announce",
+            PieceLength::Fixed(MIN_PIECE_LENGTH),
+        )
+        .unwrap();
+
+        let mut content = vec![2u8; 10_000];
+        content.extend_from_slice(&[1u8; 10_000]);
+        let expected: Vec<[u8; 20]> = content
+            .chunks(MIN_PIECE_LENGTH as usize)
+            .map(|chunk| Sha1::digest(chunk).into())
+            .collect();
+
+        assert_eq!(metainfo.info.pieces, expected);
+        if let FileInfo::Multi { files } = &metainfo.info.files {
+            assert_eq!(files[0].path, vec!["b.bin"]);
+            assert_eq!(files[1].path, vec!["disc1", "a.bin"]);
+        } else {
+            panic!("Expected multi-file torrent");
+        }
+    }
+
+    #[test]
+    fn test_create_rejects_invalid_fixed_piece_length() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, vec![0u8; 1000]).unwrap();
+
+        for length in [0, 1000, 8 * 1024, 48 * 1024, 128 * 1024 * 1024] {
+            let result = create_from_path(&path, "http://tracker.test", PieceLength::Fixed(length));
+            assert!(matches!(result, Err(crate::Error::InvalidMetainfo(_))));
+        }
+    }
+
+    fn mtimes_of(root: &Path, names: &[&str]) -> Vec<u64> {
+        names
+            .iter()
+            .map(|name| modified_nanos(&root.join(name)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_resume_matches_full_run() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("content");
+        fs::create_dir_all(&root).unwrap();
+        // Piece boundaries fall inside both files
+        fs::write(root.join("a.bin"), vec![1u8; 100_000]).unwrap();
+        fs::write(root.join("b.bin"), vec![2u8; 50_000]).unwrap();
+        let piece_length = PieceLength::Fixed(MIN_PIECE_LENGTH);
+        let state = dir.path().join("progress");
+
+        let full = create_from_path(&root, "http://tracker.test", piece_length).unwrap();
+
+        // Interrupted after 7 of 10 pieces, partway into the second file
+        let (_, info) = prepare(&root, piece_length).unwrap();
+        CreationProgress {
+            piece_length: info.piece_length,
+            layout_hash: info.layout_hash(),
+            mtimes: mtimes_of(&root, &["a.bin", "b.bin"]),
+            pieces: full.info.pieces[..7].to_vec(),
+        }
+        .save(&state)
+        .unwrap();
+        assert_eq!(CreationProgress::load(&state).unwrap().pieces_hashed(), 7);
+
+        // Progress without modification times can't be trusted
+        let without_mtimes = dir.path().join("no-mtimes");
+        fs::write(
+            &without_mtimes,
+            b"d11:layout hash20:0123456789012345678912:piece lengthi16384e6:pieces0:e",
+        )
+        .unwrap();
+        assert!(matches!(
+            CreationProgress::load(&without_mtimes),
+            Err(crate::Error::BencodeDecode { message, .. }) if message.contains("mtimes")
+        ));
+
+        let resumed =
+            create_from_path_resumable(&root, "http://tracker.test", piece_length, &state).unwrap();
+
+        assert_eq!(resumed.info.pieces, full.info.pieces);
+        assert_eq!(resumed.info_hash, full.info_hash);
+        assert!(!state.exists());
+    }
+
+    #[test]
+    fn test_resume_rehashes_modified_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("content");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.bin"), vec![1u8; 100_000]).unwrap();
+        fs::write(root.join("b.bin"), vec![2u8; 50_000]).unwrap();
+        let piece_length = PieceLength::Fixed(MIN_PIECE_LENGTH);
+        let state = dir.path().join("progress");
+
+        let original = create_from_path(&root, "http://tracker.test", piece_length).unwrap();
+        let (_, info) = prepare(&root, piece_length).unwrap();
+        CreationProgress {
+            piece_length: info.piece_length,
+            layout_hash: info.layout_hash(),
+            mtimes: mtimes_of(&root, &["a.bin", "b.bin"]),
+            pieces: original.info.pieces[..8].to_vec(),
+        }
+        .save(&state)
+        .unwrap();
+
+        // Same size, so the layout still matches, but different content
+        let changed = File::options()
+            .write(true)
+            .open(root.join("b.bin"))
+            .unwrap();
+        (&changed).write_all(&[9u8; 50_000]).unwrap();
+        changed
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        let (_, info) = prepare(&root, piece_length).unwrap();
+        let mtimes = mtimes_of(&root, &["a.bin", "b.bin"]);
+        // The 6 pieces wholly inside `a.bin` are kept
+        assert_eq!(
+            CreationProgress::load(&state)
+                .unwrap()
+                .valid_pieces(&info, &mtimes),
+            Some(6)
+        );
+
+        let resumed =
+            create_from_path_resumable(&root, "http://tracker.test", piece_length, &state).unwrap();
+        let fresh = create_from_path(&root, "http://tracker.test", piece_length).unwrap();
+        assert_eq!(resumed.info.pieces, fresh.info.pieces);
+        assert_ne!(resumed.info.pieces, original.info.pieces);
+    }
+
+    #[test]
+    fn test_resume_ignores_progress_for_other_content() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, vec![5u8; 40_000]).unwrap();
+        let state = dir.path().join("progress");
+
+        CreationProgress {
+            piece_length: MIN_PIECE_LENGTH,
+            layout_hash: [0u8; 20],
+            mtimes: Vec::new(),
+            pieces: vec![[0u8; 20]; 2],
+        }
+        .save(&state)
+        .unwrap();
+
+        let resumed =
+            create_from_path_resumable(&path, "http://tracker.test", PieceLength::Auto, &state)
+                .unwrap();
+        let full = create_from_path(&path, "http://tracker.test", PieceLength::Auto).unwrap();
+        assert_eq!(resumed.info.pieces, full.info.pieces);
+    }
+
+    #[test]
+    fn test_created_torrent_round_trips_info_hash() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, vec![3u8; 40_000]).unwrap();
+
+        let created = create_from_path(&path, "http://tracker.test", PieceLength::Auto).unwrap();
+        let bencoded = serde_bencode::to_bytes(&created.info.to_bencode()).unwrap();
+        let mut torrent = b"d8:announce19:http://tracker.test4:info".to_vec();
+        torrent.extend_from_slice(&bencoded);
+        torrent.push(b'e');
+
+        let parsed = Metainfo::from_bytes(&torrent).unwrap();
+        assert_eq!(parsed.info_hash, created.info_hash);
+    }
+}
@@ -0,0 +1,65 @@
+//! Guessing the character encoding of torrent names
+//!
+//! Torrents made by older clients without an `encoding` key often store the
+//! name in the creator's system code page. With the `chardetng` feature the
+//! guess comes from the `chardetng` detector used by Firefox.
+
+use super::Metainfo;
+
+impl Metainfo {
+    /// Likely encoding of the torrent name
+    ///
+    /// Returns `"UTF-8"` when the name is valid UTF-8. Otherwise, with the
+    /// `chardetng` feature, returns the name of the most likely legacy
+    /// encoding (such as `"Shift_JIS"`); without it, `None`.
+    pub fn detect_name_encoding(&self) -> Option<&str> {
+        let bytes = &self.info.name_bytes;
+        if std::str::from_utf8(bytes).is_ok() {
+            return Some("UTF-8");
+        }
+        guess_legacy(bytes)
+    }
+}
+
+#[cfg(feature = "chardetng")]
+fn guess_legacy(bytes: &[u8]) -> Option<&'static str> {
+    use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(bytes, true);
+    Some(detector.guess(None, Utf8Detection::Deny).name())
+}
+
+#[cfg(not(feature = "chardetng"))]
+fn guess_legacy(_bytes: &[u8]) -> Option<&'static str> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent_named(name: &[u8]) -> Metainfo {
+        let mut torrent = b"d8:announce9:localhost4:infod6:lengthi1000e4:name".to_vec();
+        torrent.extend_from_slice(format!("{}:", name.len()).as_bytes());
+        torrent.extend_from_slice(name);
+        torrent.extend_from_slice(b"12:piece lengthi1024e6:pieces20:12345678901234567890ee");
+        Metainfo::from_bytes(&torrent).unwrap()
+    }
+
+    #[test]
+    fn test_utf8_name() {
+        let metainfo = torrent_named("テスト".as_bytes());
+        assert_eq!(metainfo.detect_name_encoding(), Some("UTF-8"));
+    }
+
+    #[cfg(feature = "chardetng")]
+    #[test]
+    fn test_shift_jis_name() {
+        // "テスト.txt" in Shift_JIS
+        let metainfo = torrent_named(b"\x83\x65\x83\x58\x83\x67.txt");
+
+        assert_eq!(metainfo.info.name_bytes, b"\x83\x65\x83\x58\x83\x67.txt");
+        assert_eq!(metainfo.detect_name_encoding(), Some("Shift_JIS"));
+    }
+}
@@ -0,0 +1,613 @@
+//! Mapping between pieces and files
+//!
+//! Pieces are laid out over the concatenation of all files in torrent order,
+//! so a single piece may span the end of one file and the start of the next.
+
+use super::{FileEntry, FileInfo, Info};
+use crate::Bitfield;
+use sha1::{Digest, Sha1};
+use std::ops::{Range, RangeInclusive};
+use std::path::PathBuf;
+
+/// How a piece sits relative to file boundaries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PieceLayout {
+    /// The whole piece lies inside one file, starting at `offset`
+    Aligned { file_index: usize, offset: u64 },
+    /// The piece spans the end of one file and the start of the next;
+    /// `file_indices` lists every file it overlaps, skipping empty ones
+    Straddling { file_indices: Vec<usize> },
+}
+
+impl Info {
+    /// All files in torrent order, with paths relative to the download directory
+    ///
+    /// For a single-file torrent this is one entry named after the torrent.
+    /// For a multi-file torrent each path is prefixed with the torrent name,
+    /// which is the directory the files live in.
+    pub fn files(&self) -> Vec<FileEntry> {
+        match &self.files {
+            FileInfo::Single { length } => vec![FileEntry {
+                path: vec![self.name.clone()],
+                length: *length,
+                attributes: String::new(),
+            }],
+            FileInfo::Multi { files } => files
+                .iter()
+                .map(|f| FileEntry {
+                    path: std::iter::once(self.name.clone())
+                        .chain(f.path.iter().cloned())
+                        .collect(),
+                    length: f.length,
+                    attributes: f.attributes.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Total size of all files in bytes
+    pub fn total_size(&self) -> u64 {
+        match &self.files {
+            FileInfo::Single { length } => *length,
+            FileInfo::Multi { files } => files.iter().map(|f| f.length).sum(),
+        }
+    }
+
+    /// Size of the real content in bytes, leaving out BEP 47 padding files
+    pub fn content_size(&self) -> u64 {
+        match &self.files {
+            FileInfo::Single { length } => *length,
+            FileInfo::Multi { files } => files
+                .iter()
+                .filter(|f| !f.is_padding())
+                .map(|f| f.length)
+                .sum(),
+        }
+    }
+
+    /// SHA-1 over the sorted `(path, size)` pairs of the content; see
+    /// [`Metainfo::layout_hash`](super::Metainfo::layout_hash)
+    ///
+    /// Padding files depend on the piece length, so they are left out.
+    pub fn layout_hash(&self) -> [u8; 20] {
+        let mut files: Vec<(String, u64)> = self
+            .files()
+            .into_iter()
+            .filter(|f| !f.is_padding())
+            .map(|f| (f.path.join("/"), f.length))
+            .collect();
+        files.sort();
+
+        let mut hasher = Sha1::new();
+        for (path, length) in files {
+            hasher.update(path.as_bytes());
+            hasher.update([0]);
+            hasher.update(length.to_be_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    /// Root of a binary Merkle tree over the v1 piece hashes
+    ///
+    /// Each parent is the SHA-1 of its two children concatenated; an odd
+    /// node out is carried up a level unchanged. Unlike the info hash this
+    /// depends only on the piece set, so it survives edits to the name or
+    /// other metadata. With no pieces the root is all zeros.
+    pub fn pieces_merkle_root(&self) -> [u8; 20] {
+        let mut level = self.pieces.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => Sha1::new()
+                        .chain_update(left)
+                        .chain_update(right)
+                        .finalize()
+                        .into(),
+                    _ => pair[0],
+                })
+                .collect();
+        }
+        level.first().copied().unwrap_or([0; 20])
+    }
+
+    /// Where each part of a received piece must be written
+    ///
+    /// Returns `(file_index, file_offset, buffer_range)` for every file the
+    /// piece overlaps: the bytes `buffer_range` of the piece belong in file
+    /// `file_index` (as ordered by [`Info::files`]) starting at `file_offset`.
+    /// An out-of-range piece index yields an empty plan.
+    pub fn piece_write_plan(&self, piece_index: usize) -> Vec<(usize, u64, Range<usize>)> {
+        let Some(piece) = self.piece_range(piece_index) else {
+            return Vec::new();
+        };
+
+        self.file_ranges()
+            .enumerate()
+            .filter_map(|(index, file)| {
+                let start = piece.start.max(file.start);
+                let end = piece.end.min(file.end);
+                (start < end).then(|| {
+                    (
+                        index,
+                        start - file.start,
+                        (start - piece.start) as usize..(end - piece.start) as usize,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Number of piece hashes in the info dictionary
+    ///
+    /// Equal to `pieces.len()` unless the hashes were skipped with
+    /// [`Metainfo::from_bytes_metadata_only`](super::Metainfo::from_bytes_metadata_only),
+    /// in which case it is the count they declared.
+    pub fn num_pieces(&self) -> usize {
+        self.skipped_pieces.unwrap_or(self.pieces.len())
+    }
+
+    /// Number of pieces needed to cover the content, from its size and the
+    /// piece length rather than the number of hashes
+    pub fn piece_count(&self) -> u64 {
+        if self.piece_length == 0 {
+            return 0;
+        }
+        self.total_size().div_ceil(self.piece_length)
+    }
+
+    /// Global byte range `(start, end)` of a piece, the last one possibly
+    /// short
+    ///
+    /// An out-of-range index gives an empty range at the end of the content.
+    pub fn piece_bounds(&self, piece_index: usize) -> (u64, u64) {
+        match self.piece_range(piece_index) {
+            Some(range) => (range.start, range.end),
+            None => (self.total_size(), self.total_size()),
+        }
+    }
+
+    /// Files of a multi-file torrent that a piece overlaps, each with the
+    /// byte range of that file the piece covers
+    ///
+    /// Empty files are skipped. A single-file torrent has no `FileEntry`,
+    /// so it, like an out-of-range index, yields nothing.
+    pub fn files_in_piece(&self, piece_index: usize) -> Vec<(&FileEntry, Range<u64>)> {
+        let (FileInfo::Multi { files }, Some(piece)) = (&self.files, self.piece_range(piece_index))
+        else {
+            return Vec::new();
+        };
+
+        files
+            .iter()
+            .zip(self.file_ranges())
+            .filter_map(|(file, range)| {
+                let start = piece.start.max(range.start);
+                let end = piece.end.min(range.end);
+                (start < end).then(|| (file, start - range.start..end - range.start))
+            })
+            .collect()
+    }
+
+    /// Pieces that must be present to serve a byte range of the content
+    ///
+    /// The range is clamped to the content size. An empty result (such as
+    /// `1..=0`) means the clamped range covers no bytes.
+    pub fn pieces_for_byte_range(&self, range: Range<u64>) -> RangeInclusive<usize> {
+        let end = range.end.min(self.total_size());
+        if range.start >= end || self.piece_length == 0 {
+            return RangeInclusive::new(1, 0);
+        }
+        let first = range.start / self.piece_length;
+        let last = (end - 1) / self.piece_length;
+        first as usize..=last as usize
+    }
+
+    /// Files whose every overlapping piece is in `have`, as paths relative to
+    /// the download directory
+    ///
+    /// A piece shared with a neighbouring file must be present too, since a
+    /// file can't be verified without it. Empty files are always complete.
+    /// Padding files are never listed.
+    pub fn completed_files(&self, have: &Bitfield) -> Vec<PathBuf> {
+        self.files()
+            .into_iter()
+            .zip(self.file_ranges())
+            .filter(|(file, range)| {
+                !file.is_padding()
+                    && self
+                        .pieces_for_byte_range(range.clone())
+                        .all(|i| have.has(i))
+            })
+            .map(|(file, _)| file.path.iter().collect())
+            .collect()
+    }
+
+    /// Pieces needed to download exactly the files at `indices` (as ordered
+    /// by [`Info::files`])
+    ///
+    /// Includes boundary pieces shared with unselected neighbours, since the
+    /// selected file can't be verified without them. Out-of-range indices
+    /// are ignored.
+    pub fn select_files(&self, indices: &[usize]) -> Bitfield {
+        let ranges: Vec<Range<u64>> = self.file_ranges().collect();
+        let mut needed = Bitfield::new(self.num_pieces());
+        for range in indices.iter().filter_map(|&i| ranges.get(i)) {
+            for piece in self.pieces_for_byte_range(range.clone()) {
+                needed.set(piece);
+            }
+        }
+        needed
+    }
+
+    /// Expected length in bytes of a peer's `bitfield` message payload
+    ///
+    /// One bit per piece, rounded up to whole bytes.
+    pub fn bitfield_len(&self) -> usize {
+        self.num_pieces().div_ceil(8)
+    }
+
+    /// Check a peer's `bitfield` payload against this torrent
+    ///
+    /// Fails with `Error::Peer` if the length differs from
+    /// [`Info::bitfield_len`] or any spare bit after the last piece is set.
+    pub fn validate_bitfield(&self, bits: &[u8]) -> crate::Result<Bitfield> {
+        if bits.len() != self.bitfield_len() {
+            return Err(crate::Error::Peer(format!(
+                "Bitfield is {} bytes, expected {}",
+                bits.len(),
+                self.bitfield_len()
+            )));
+        }
+
+        let spare = self.bitfield_len() * 8 - self.num_pieces();
+        let spare_mask = ((1u16 << spare) - 1) as u8;
+        if bits.last().is_some_and(|last| last & spare_mask != 0) {
+            return Err(crate::Error::Peer(
+                "Bitfield has spare bits set".to_string(),
+            ));
+        }
+
+        Ok(Bitfield::from_raw(bits.to_vec(), self.num_pieces()))
+    }
+
+    /// Check that a block of `length` bytes at offset `begin` lies within
+    /// piece `piece_index`
+    ///
+    /// Uses the piece's actual size, so the short last piece is accounted
+    /// for. Fails with `Error::Peer` for an unknown piece, an empty block or
+    /// one running past the end of the piece.
+    pub fn validate_block(&self, piece_index: usize, begin: u32, length: u32) -> crate::Result<()> {
+        let Some(piece) = self.piece_range(piece_index) else {
+            return Err(crate::Error::Peer(format!(
+                "Block for piece {} beyond the last piece",
+                piece_index
+            )));
+        };
+        let piece_size = piece.end - piece.start;
+        let end = u64::from(begin) + u64::from(length);
+        if length == 0 || end > piece_size {
+            return Err(crate::Error::Peer(format!(
+                "Block {}..{} out of bounds for piece {} of {} bytes",
+                begin, end, piece_index, piece_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Layout of every piece relative to file boundaries, in piece order
+    ///
+    /// Aligned pieces can be written with a single positioned write, which
+    /// lets callers pick a cheaper strategy than for straddling ones.
+    pub fn aligned_piece_map(&self) -> Vec<PieceLayout> {
+        let files: Vec<Range<u64>> = self.file_ranges().collect();
+        let mut first_file = 0;
+
+        (0..self.num_pieces())
+            .map_while(|index| self.piece_range(index))
+            .map(|piece| {
+                // Pieces only move forward, so files ending before this one
+                // can never overlap a later piece either
+                while files.get(first_file).is_some_and(|f| f.end <= piece.start) {
+                    first_file += 1;
+                }
+                let overlapping: Vec<usize> = files[first_file..]
+                    .iter()
+                    .take_while(|f| f.start < piece.end)
+                    .enumerate()
+                    .filter(|(_, f)| f.start < f.end)
+                    .map(|(i, _)| first_file + i)
+                    .collect();
+
+                match overlapping[..] {
+                    [file_index] => PieceLayout::Aligned {
+                        file_index,
+                        offset: piece.start - files[file_index].start,
+                    },
+                    _ => PieceLayout::Straddling {
+                        file_indices: overlapping,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Global byte range covered by a piece, accounting for a short last piece
+    fn piece_range(&self, piece_index: usize) -> Option<Range<u64>> {
+        let start = (piece_index as u64).checked_mul(self.piece_length)?;
+        let total = self.total_size();
+        (start < total).then(|| start..(start + self.piece_length).min(total))
+    }
+
+    /// Global byte range covered by each file, in torrent order
+    fn file_ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        let lengths: Vec<u64> = match &self.files {
+            FileInfo::Single { length } => vec![*length],
+            FileInfo::Multi { files } => files.iter().map(|f| f.length).collect(),
+        };
+        lengths.into_iter().scan(0u64, |offset, length| {
+            let range = *offset..*offset + length;
+            *offset += length;
+            Some(range)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multi_file_info(lengths: &[u64], piece_length: u64) -> Info {
+        let total: u64 = lengths.iter().sum();
+        let num_pieces = total.div_ceil(piece_length) as usize;
+        Info {
+            name: "dir".to_string(),
+            name_bytes: b"dir".to_vec(),
+            piece_length,
+            pieces: vec![[0u8; 20]; num_pieces],
+            skipped_pieces: None,
+            files: FileInfo::Multi {
+                files: lengths
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &length)| FileEntry {
+                        path: vec![format!("file{}", i)],
+                        length,
+                        attributes: String::new(),
+                    })
+                    .collect(),
+            },
+            private: false,
+        }
+    }
+
+    #[test]
+    fn test_files_prefixes_torrent_name() {
+        let info = multi_file_info(&[10, 20], 16);
+        let files = info.files();
+        assert_eq!(files[0].path, vec!["dir", "file0"]);
+        assert_eq!(files[1].path, vec!["dir", "file1"]);
+        assert_eq!(info.total_size(), 30);
+    }
+
+    #[test]
+    fn test_piece_write_plan_spanning_two_files() {
+        let info = multi_file_info(&[1000, 2000], 512);
+
+        // Piece 1 covers bytes 512..1024: the tail of file0 and head of file1
+        assert_eq!(
+            info.piece_write_plan(1),
+            vec![(0, 512, 0..488), (1, 0, 488..512)]
+        );
+    }
+
+    #[test]
+    fn test_pieces_merkle_root() {
+        let mut info = multi_file_info(&[1000, 2000], 512);
+        for (i, piece) in info.pieces.iter_mut().enumerate() {
+            piece[0] = i as u8;
+        }
+        let root = info.pieces_merkle_root();
+        assert_eq!(root, info.clone().pieces_merkle_root());
+
+        // Metadata doesn't matter, the pieces do
+        let mut renamed = info.clone();
+        renamed.name = "other".to_string();
+        assert_eq!(renamed.pieces_merkle_root(), root);
+
+        let mut changed = info.clone();
+        changed.pieces[5][1] = 1;
+        assert_ne!(changed.pieces_merkle_root(), root);
+
+        changed.pieces.truncate(1);
+        assert_eq!(changed.pieces_merkle_root(), changed.pieces[0]);
+    }
+
+    #[test]
+    fn test_piece_straddling_two_files() {
+        let info = multi_file_info(&[1000, 0, 2000], 512);
+        assert_eq!(info.piece_count(), 6);
+        assert_eq!(info.piece_bounds(1), (512, 1024));
+        assert_eq!(info.piece_bounds(5), (2560, 3000));
+        assert_eq!(info.piece_bounds(6), (3000, 3000));
+
+        // Piece 1 holds the tail of file0 and the head of file2
+        let files: Vec<(&str, Range<u64>)> = info
+            .files_in_piece(1)
+            .into_iter()
+            .map(|(file, range)| (file.path[0].as_str(), range))
+            .collect();
+        assert_eq!(files, vec![("file0", 512..1000), ("file2", 0..24)]);
+
+        assert_eq!(info.files_in_piece(5).len(), 1);
+        assert!(info.files_in_piece(6).is_empty());
+    }
+
+    #[test]
+    fn test_pieces_for_byte_range() {
+        let info = multi_file_info(&[1000, 2000], 512);
+
+        // Within one piece
+        assert_eq!(info.pieces_for_byte_range(600..700), 1..=1);
+        // Spanning pieces, ending exactly on a boundary
+        assert_eq!(info.pieces_for_byte_range(100..1024), 0..=1);
+        assert_eq!(info.pieces_for_byte_range(511..1025), 0..=2);
+        // At the end of the content, clamped to the short last piece
+        assert_eq!(info.pieces_for_byte_range(2900..10_000), 5..=5);
+        // Empty or entirely out of bounds
+        assert!(info.pieces_for_byte_range(700..700).is_empty());
+        assert!(info.pieces_for_byte_range(3000..4000).is_empty());
+    }
+
+    #[test]
+    fn test_bitfield_len() {
+        // 3000 bytes in 512-byte pieces is 6 pieces: one byte with spare bits
+        assert_eq!(multi_file_info(&[1000, 2000], 512).bitfield_len(), 1);
+        // 9 pieces need a second byte
+        assert_eq!(multi_file_info(&[9 * 16], 16).bitfield_len(), 2);
+        assert_eq!(multi_file_info(&[8 * 16], 16).bitfield_len(), 1);
+    }
+
+    #[test]
+    fn test_aligned_piece_map() {
+        let info = multi_file_info(&[1024, 0, 700, 300], 512);
+
+        assert_eq!(
+            info.aligned_piece_map(),
+            vec![
+                PieceLayout::Aligned {
+                    file_index: 0,
+                    offset: 0
+                },
+                PieceLayout::Aligned {
+                    file_index: 0,
+                    offset: 512
+                },
+                PieceLayout::Aligned {
+                    file_index: 2,
+                    offset: 0
+                },
+                // Bytes 1536..2024: the tail of file2 and all of file3
+                PieceLayout::Straddling {
+                    file_indices: vec![2, 3]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_completed_files_shared_boundary_piece() {
+        // file0 is pieces 0..=1, file1 is pieces 1..=5 and file2 is empty
+        let info = multi_file_info(&[1000, 2000, 0], 512);
+        let mut have = Bitfield::new(info.num_pieces());
+        for piece in 0..=4 {
+            have.set(piece);
+        }
+
+        assert_eq!(
+            info.completed_files(&have),
+            vec![PathBuf::from("dir/file0"), PathBuf::from("dir/file2")]
+        );
+
+        have.set(5);
+        assert_eq!(info.completed_files(&have).len(), 3);
+
+        // Without the shared piece neither neighbour is complete
+        let mut have = Bitfield::new(info.num_pieces());
+        for piece in [0, 2, 3, 4, 5] {
+            have.set(piece);
+        }
+        assert_eq!(
+            info.completed_files(&have),
+            vec![PathBuf::from("dir/file2")]
+        );
+    }
+
+    #[test]
+    fn test_select_files_includes_boundary_pieces() {
+        // file0 is bytes 0..1000, file1 1000..1600, file2 1600..3000
+        let info = multi_file_info(&[1000, 600, 1400], 512);
+
+        // file1 needs piece 1 (shared with file0), piece 2 and piece 3
+        // (shared with file2)
+        let needed = info.select_files(&[1]);
+        assert_eq!(
+            (0..6).filter(|&i| needed.has(i)).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        assert_eq!(info.select_files(&[0, 2]).count(), 5);
+        assert_eq!(info.select_files(&[7]).count(), 0);
+    }
+
+    #[test]
+    fn test_validate_bitfield() {
+        // 6 pieces: the low two bits of the only byte are spare
+        let info = multi_file_info(&[1000, 2000], 512);
+
+        let bitfield = info.validate_bitfield(&[0b1010_0100]).unwrap();
+        assert_eq!(bitfield.len(), 6);
+        assert!(bitfield.has(0) && bitfield.has(2) && bitfield.has(5));
+        assert_eq!(bitfield.count(), 3);
+
+        assert!(matches!(
+            info.validate_bitfield(&[0xFC, 0x00]),
+            Err(crate::Error::Peer(_))
+        ));
+        assert!(matches!(
+            info.validate_bitfield(&[0b1111_1101]),
+            Err(crate::Error::Peer(_))
+        ));
+
+        // A whole number of bytes has no spare bits to check
+        let info = multi_file_info(&[8 * 16], 16);
+        assert!(info.validate_bitfield(&[0xFF]).unwrap().is_complete());
+    }
+
+    #[test]
+    fn test_validate_block() {
+        // 6 pieces of 512 bytes, the last one 440
+        let info = multi_file_info(&[1000, 2000], 512);
+
+        assert!(info.validate_block(0, 0, 512).is_ok());
+        assert!(info.validate_block(2, 256, 256).is_ok());
+        assert!(info.validate_block(5, 0, 440).is_ok());
+        assert!(info.validate_block(5, 400, 40).is_ok());
+
+        for (piece, begin, length) in [
+            (0, 0, 513),
+            (0, 512, 1),
+            (5, 0, 512),
+            (5, 400, 41),
+            (6, 0, 1),
+            (0, 0, 0),
+            (0, u32::MAX, 2),
+        ] {
+            assert!(
+                matches!(
+                    info.validate_block(piece, begin, length),
+                    Err(crate::Error::Peer(_))
+                ),
+                "piece {} block {}+{}",
+                piece,
+                begin,
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn test_piece_write_plan_edges() {
+        let info = multi_file_info(&[1000, 0, 2000], 512);
+
+        assert_eq!(info.piece_write_plan(0), vec![(0, 0, 0..512)]);
+        // Zero-length files never receive data
+        assert_eq!(
+            info.piece_write_plan(1),
+            vec![(0, 512, 0..488), (2, 0, 488..512)]
+        );
+        // Last piece is short: 3000 - 5 * 512 = 440 bytes
+        assert_eq!(info.piece_write_plan(5), vec![(2, 1560, 0..440)]);
+        assert!(info.piece_write_plan(6).is_empty());
+    }
+}
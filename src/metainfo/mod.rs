@@ -0,0 +1,2178 @@
+//! Torrent metainfo file (.torrent) parsing
+//!
+//! A .torrent file contains all the information needed to download a file:
+//! - Tracker URL(s)
+//! - File information (name, length, piece hashes)
+//! - Optional metadata (creation date, comments)
+
+mod category;
+pub mod create;
+mod encoding;
+mod layout;
+mod storage;
+
+pub use category::ContentCategory;
+pub use create::{
+    CreationProgress, PieceLength, create_from_path, create_from_path_resumable,
+    recommended_piece_length,
+};
+pub use layout::PieceLayout;
+
+use crate::Bitfield;
+use crate::bencode::{self, Value};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use percent_encoding::percent_decode_str;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Largest piece length [`Metainfo::validate`] accepts and torrent creation
+/// allows (64 MiB)
+const MAX_PIECE_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// Most trackers [`Metainfo::probe_trackers`] probes at once
+const MAX_TRACKER_PROBES: usize = 8;
+
+/// Parsed .torrent file
+#[derive(Debug, Clone)]
+pub struct Metainfo {
+    /// Primary tracker URL; `None` for trackerless (DHT-only) torrents
+    pub announce: Option<String>,
+    /// List of backup trackers
+    pub announce_list: Vec<Vec<String>>,
+    /// SHA-1 hash of the info dictionary (identifies the torrent)
+    pub info_hash: [u8; 20],
+    /// Detailed file information
+    pub info: Info,
+    /// The `info` dictionary exactly as parsed, which [`Metainfo::to_bytes`]
+    /// writes back unchanged so the info hash survives re-encoding
+    ///
+    /// `None` for torrents built in code and for metadata-only parses, which
+    /// re-encode [`Metainfo::info`] instead. While this is set, edits to
+    /// `info` are not written out.
+    pub info_bytes: Option<Vec<u8>>,
+    /// Optional creation timestamp
+    pub creation_date: Option<i64>,
+    /// Optional comment, lossily decoded as UTF-8
+    pub comment: Option<String>,
+    /// Raw comment bytes as stored in the torrent
+    pub comment_bytes: Option<Vec<u8>>,
+    /// Optional creator, lossily decoded as UTF-8
+    pub created_by: Option<String>,
+    /// Raw creator bytes as stored in the torrent
+    pub created_by_bytes: Option<Vec<u8>>,
+    /// DHT nodes (`nodes`) as host/port pairs, exactly as listed
+    pub nodes: Vec<(String, u16)>,
+    /// IPv6 DHT nodes (`nodes6`), in the same form as `nodes`
+    pub nodes6: Vec<(String, u16)>,
+    /// Web seed URLs (`url-list`, BEP 19), lossily decoded as UTF-8
+    pub url_list: Vec<String>,
+    /// `meta version` from the info dictionary, if present
+    ///
+    /// Some v1 torrents carry `meta version: 1`; the torrent is parsed as v1
+    /// whatever the value.
+    pub meta_version: Option<u32>,
+}
+
+/// Relaxations for parsing torrents written by non-conforming tools
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Decode the first complete value and ignore anything after it, instead
+    /// of rejecting the torrent
+    pub allow_trailing_bytes: bool,
+}
+
+/// File information from the info dictionary
+#[derive(Debug, Clone)]
+pub struct Info {
+    /// Suggested name for the file/directory, lossily decoded as UTF-8
+    pub name: String,
+    /// Raw name bytes as stored in the torrent
+    pub name_bytes: Vec<u8>,
+    /// Length of each piece in bytes (typically 256KB or 512KB)
+    pub piece_length: u64,
+    /// Concatenated SHA-1 hashes of all pieces
+    pub pieces: Vec<[u8; 20]>,
+    /// Piece count from the info dictionary when the hashes themselves were
+    /// skipped with [`Metainfo::from_bytes_metadata_only`]
+    skipped_pieces: Option<usize>,
+    /// Single file or multiple files
+    pub files: FileInfo,
+    /// Set by `private: 1` (BEP 27), and by any other value except `0`
+    ///
+    /// Peers of a private torrent must only come from its trackers, so DHT
+    /// and peer exchange must stay disabled for it.
+    pub private: bool,
+}
+
+/// File layout - either single file or multiple files
+#[derive(Debug, Clone)]
+pub enum FileInfo {
+    /// Single file torrent
+    Single {
+        /// Length in bytes
+        length: u64,
+    },
+    /// Multi-file torrent
+    Multi {
+        /// List of files with paths and lengths
+        files: Vec<FileEntry>,
+    },
+}
+
+/// A single file in a multi-file torrent
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    /// Path components (e.g., ["subdir", "file.txt"])
+    pub path: Vec<String>,
+    /// Length in bytes
+    pub length: u64,
+    /// BEP 47 `attr` flags, one character each (empty when absent)
+    ///
+    /// `p` marks a padding file, `x` an executable, `h` a hidden file and
+    /// `l` a symlink.
+    pub attributes: String,
+}
+
+impl FileEntry {
+    /// Whether this is a BEP 47 padding file
+    ///
+    /// Padding files only align the next file to a piece boundary. Their
+    /// content is all zeros and they should not be written to disk.
+    pub fn is_padding(&self) -> bool {
+        self.attributes.contains('p')
+    }
+
+    /// Whether the file should be marked executable
+    pub fn is_executable(&self) -> bool {
+        self.attributes.contains('x')
+    }
+}
+
+// Internal structures for encoding the info dictionary
+#[derive(Deserialize, Serialize)]
+struct BencodeInfo {
+    #[serde(with = "serde_bytes")]
+    name: Vec<u8>,
+    #[serde(rename = "piece length")]
+    piece_length: u64,
+    #[serde(with = "serde_bytes")]
+    pieces: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    private: Option<i64>,
+    #[serde(
+        rename = "meta version",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    meta_version: Option<u32>,
+    #[serde(flatten)]
+    file_info: BencodeFileInfo,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum BencodeFileInfo {
+    Single { length: u64 },
+    Multi { files: Vec<BencodeFile> },
+}
+
+/// Top-level dictionary written by [`Metainfo::to_bytes`]
+#[derive(Serialize)]
+struct BencodeTorrent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    announce: Option<String>,
+    #[serde(rename = "announce-list", skip_serializing_if = "Vec::is_empty")]
+    announce_list: Vec<Vec<String>>,
+    #[serde(rename = "creation date", skip_serializing_if = "Option::is_none")]
+    creation_date: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+    comment: Option<Vec<u8>>,
+    #[serde(
+        rename = "created by",
+        skip_serializing_if = "Option::is_none",
+        with = "serde_bytes"
+    )]
+    created_by: Option<Vec<u8>>,
+    info: BencodeInfo,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nodes: Vec<(String, u16)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nodes6: Vec<(String, u16)>,
+    #[serde(rename = "url-list", skip_serializing_if = "Vec::is_empty")]
+    url_list: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct BencodeFile {
+    path: Vec<String>,
+    length: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attr: Option<String>,
+}
+
+impl Metainfo {
+    /// Parse a .torrent file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parse a torrent embedded in a `data:` URI, as some web APIs return
+    /// them: `data:application/x-bittorrent;base64,<payload>`
+    ///
+    /// The media type must be `application/x-bittorrent` and the payload
+    /// base64; other parameters are ignored. Percent-escapes and whitespace
+    /// in the payload are tolerated. Fails with `Error::InvalidMetainfo`
+    /// for other URIs and undecodable payloads.
+    pub fn from_data_uri(uri: &str) -> crate::Result<Self> {
+        let rest = uri
+            .get(..5)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+            .map(|_| &uri[5..])
+            .ok_or_else(|| invalid("not a data URI"))?;
+        let (header, payload) = rest
+            .split_once(',')
+            .ok_or_else(|| invalid("data URI has no payload"))?;
+
+        let mut params = header.split(';').map(str::trim);
+        let mime = params.next().unwrap_or_default();
+        if !mime.eq_ignore_ascii_case("application/x-bittorrent") {
+            return Err(invalid(format!(
+                "data URI media type is `{}`, expected `application/x-bittorrent`",
+                mime
+            )));
+        }
+        if !params.any(|param| param.eq_ignore_ascii_case("base64")) {
+            return Err(invalid("data URI payload is not base64"));
+        }
+
+        let payload: Vec<u8> = percent_decode_str(payload)
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        let bytes = BASE64
+            .decode(payload)
+            .map_err(|e| invalid(format!("invalid base64 in data URI: {}", e)))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Encode as .torrent data
+    ///
+    /// The info dictionary is written from [`Metainfo::info_bytes`] when set,
+    /// keeping keys this crate doesn't model (such as `source`). Otherwise it
+    /// is rebuilt from [`Metainfo::info`] and must hash to
+    /// [`Metainfo::info_hash`]; this fails with `Error::InvalidMetainfo` when
+    /// it doesn't, such as when the piece hashes were skipped while parsing.
+    /// Top-level keys that aren't modelled are dropped.
+    ///
+    /// Edited `comment` and `created_by` values are written as they are;
+    /// unedited ones keep their original bytes.
+    pub fn to_bytes(&self) -> crate::Result<Vec<u8>> {
+        let raw_info = self
+            .info_bytes
+            .as_deref()
+            .filter(|raw| <[u8; 20]>::from(Sha1::digest(raw)) == self.info_hash);
+        let mut info = self.info.to_bencode();
+        info.meta_version = self.meta_version;
+        if raw_info.is_none() && hash_info(&info)? != self.info_hash {
+            return Err(invalid(
+                "info dictionary cannot be re-encoded with the same info hash",
+            ));
+        }
+
+        let torrent = BencodeTorrent {
+            announce: self.announce.clone(),
+            announce_list: self.announce_list.clone(),
+            creation_date: self.creation_date,
+            comment: stored_text(&self.comment, &self.comment_bytes),
+            created_by: stored_text(&self.created_by, &self.created_by_bytes),
+            info,
+            nodes: self.nodes.clone(),
+            nodes6: self.nodes6.clone(),
+            url_list: self.url_list.clone(),
+        };
+        let bytes = serde_bencode::to_bytes(&torrent)
+            .map_err(|e| crate::Error::BencodeEncode(e.to_string()))?;
+        let Some(raw_info) = raw_info else {
+            return Ok(bytes);
+        };
+
+        // Swap the rebuilt dictionary for the original bytes
+        let (_, span, _) = bencode::decode_with_key_span(&bytes, "info")?;
+        let span = span.ok_or_else(|| invalid("missing field `info`"))?;
+        let mut spliced = Vec::with_capacity(bytes.len() - span.len() + raw_info.len());
+        spliced.extend_from_slice(&bytes[..span.start]);
+        spliced.extend_from_slice(raw_info);
+        spliced.extend_from_slice(&bytes[span.end..]);
+        Ok(spliced)
+    }
+
+    /// Write as a .torrent file; see [`Metainfo::to_bytes`]
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// The literal bytes of the `info` dictionary within .torrent data
+    ///
+    /// This is exactly what the info hash is computed over, whatever the
+    /// key order or extra keys in the dictionary.
+    pub fn raw_info(bytes: &[u8]) -> crate::Result<&[u8]> {
+        let (_, info_span, _) = bencode::decode_with_key_span(bytes, "info")
+            .map_err(|e| crate::Error::InvalidMetainfo(e.to_string()))?;
+        let info_span = info_span.ok_or_else(|| invalid("missing field `info`"))?;
+        Ok(&bytes[info_span])
+    }
+
+    /// Parse .torrent data from bytes
+    ///
+    /// The input is decoded in a single pass. The info hash is the SHA-1 of
+    /// the literal `info` dictionary bytes, so it matches what other clients
+    /// compute even when the dictionary isn't canonically encoded.
+    pub fn from_bytes(bytes: &[u8]) -> crate::Result<Self> {
+        Self::from_bytes_with_options(bytes, &ParseOptions::default())
+    }
+
+    /// Parse .torrent data from bytes, relaxing checks as `options` allow
+    pub fn from_bytes_with_options(bytes: &[u8], options: &ParseOptions) -> crate::Result<Self> {
+        Self::parse(bytes, false, options)
+    }
+
+    /// Parse everything except the piece hashes
+    ///
+    /// `info.pieces` is left empty while `num_pieces()` still reports the
+    /// declared count. This avoids copying the hashes, which dominate the
+    /// size of most torrents, when only the metadata is needed (e.g. for
+    /// indexing).
+    pub fn from_bytes_metadata_only(bytes: &[u8]) -> crate::Result<Self> {
+        Self::parse(bytes, true, &ParseOptions::default())
+    }
+
+    fn parse(bytes: &[u8], metadata_only: bool, options: &ParseOptions) -> crate::Result<Self> {
+        let (value, info_span, end) = bencode::decode_with_key_span(bytes, "info")
+            .map_err(|e| crate::Error::InvalidMetainfo(e.to_string()))?;
+        if end != bytes.len() {
+            if !options.allow_trailing_bytes {
+                return Err(invalid("trailing data after the torrent dictionary"));
+            }
+            tracing::warn!(
+                "Ignoring {} trailing bytes after the torrent dictionary",
+                bytes.len() - end
+            );
+        }
+        let root = as_dict(&value, "torrent")?;
+
+        let info_span = info_span.ok_or_else(|| invalid("missing field `info`"))?;
+        let info_hash: [u8; 20] = Sha1::digest(&bytes[info_span.clone()]).into();
+        let info_value = require(root, "info")?;
+        let info = Info::from_value(info_value, metadata_only)?;
+        let meta_version = as_dict(info_value, "info")?
+            .get("meta version")
+            .map(|v| {
+                u32::try_from(as_int(v, "meta version")?)
+                    .map_err(|_| invalid("`meta version` out of range"))
+            })
+            .transpose()?;
+
+        let announce_list = match root.get("announce-list") {
+            Some(tiers) => as_list(tiers, "announce-list")?
+                .iter()
+                .map(|tier| {
+                    // A single non-UTF-8 URL shouldn't fail the whole torrent
+                    as_list(tier, "announce-list")?
+                        .iter()
+                        .map(|url| {
+                            as_bytes(url, "announce-list")
+                                .map(|b| String::from_utf8_lossy(b).into_owned())
+                        })
+                        .collect()
+                })
+                .collect::<crate::Result<_>>()?,
+            None => Vec::new(),
+        };
+
+        // Some clients embed non-UTF-8 text here, which shouldn't fail the parse
+        let comment_bytes = root
+            .get("comment")
+            .map(|v| as_bytes(v, "comment").map(<[u8]>::to_vec))
+            .transpose()?;
+        let created_by_bytes = root
+            .get("created by")
+            .map(|v| as_bytes(v, "created by").map(<[u8]>::to_vec))
+            .transpose()?;
+
+        let nodes = |key: &str| -> crate::Result<Vec<(String, u16)>> {
+            match root.get(key) {
+                Some(nodes) => Ok(as_list(nodes, key)?.iter().filter_map(parse_node).collect()),
+                None => Ok(Vec::new()),
+            }
+        };
+
+        // A single web seed may be given as a bare string
+        let url_list = match root.get("url-list") {
+            Some(Value::List(urls)) => urls.iter().filter_map(parse_web_seed).collect(),
+            Some(url) => parse_web_seed(url).into_iter().collect(),
+            None => Vec::new(),
+        };
+
+        Ok(Metainfo {
+            announce: root
+                .get("announce")
+                .map(|v| as_string(v, "announce"))
+                .transpose()?,
+            announce_list,
+            info_hash,
+            info,
+            info_bytes: (!metadata_only).then(|| bytes[info_span].to_vec()),
+            creation_date: root
+                .get("creation date")
+                .map(|v| as_int(v, "creation date"))
+                .transpose()?,
+            comment: comment_bytes
+                .as_deref()
+                .map(|b| String::from_utf8_lossy(b).into_owned()),
+            comment_bytes,
+            created_by: created_by_bytes
+                .as_deref()
+                .map(|b| String::from_utf8_lossy(b).into_owned()),
+            created_by_bytes,
+            nodes: nodes("nodes")?,
+            nodes6: nodes("nodes6")?,
+            url_list,
+            meta_version,
+        })
+    }
+
+    /// Parse .torrent data and check that it is the torrent we expect
+    ///
+    /// Fails with `Error::InvalidMetainfo` if the computed info hash differs
+    /// from `expected`.
+    pub fn from_bytes_verify(bytes: &[u8], expected: [u8; 20]) -> crate::Result<Self> {
+        let metainfo = Self::from_bytes(bytes)?;
+        if metainfo.info_hash != expected {
+            return Err(invalid("info hash mismatch"));
+        }
+        Ok(metainfo)
+    }
+
+    /// Stricter checks than parsing applies, for rejecting malformed
+    /// torrents up front
+    ///
+    /// Fails with `Error::InvalidMetainfo` if the piece length is over
+    /// 64 MiB, or unless the number of piece hashes matches the pieces
+    /// needed to cover the content ([`Metainfo::piece_count`]), which for a
+    /// multi-file torrent is every file, padding included. Parsing stays
+    /// lenient so that odd real-world files can still be inspected.
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.info.piece_length > MAX_PIECE_LENGTH {
+            return Err(invalid(format!(
+                "piece length {} is over the 64 MiB limit",
+                self.info.piece_length
+            )));
+        }
+        let expected = self.piece_count();
+        if self.info.num_pieces() as u64 != expected {
+            return Err(invalid(format!(
+                "{} piece hashes for {} bytes, expected {}",
+                self.info.num_pieces(),
+                self.total_size(),
+                expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get total size of all files in bytes, padding files included
+    pub fn total_size(&self) -> u64 {
+        self.info.total_size()
+    }
+
+    /// Size of the real content in bytes
+    ///
+    /// Unlike [`Metainfo::total_size`], this leaves out BEP 47 padding files,
+    /// which only align files to piece boundaries.
+    pub fn content_size(&self) -> u64 {
+        self.info.content_size()
+    }
+
+    /// Free disk space to check for before starting a download
+    ///
+    /// The content size (without BEP 47 padding files, which are never
+    /// written) plus one piece length for buffering a partial piece.
+    pub fn required_disk_space(&self) -> u64 {
+        self.content_size() + self.info.piece_length
+    }
+
+    /// Like [`Metainfo::required_disk_space`], but leaving out pieces already
+    /// in `have`, for resuming a download
+    ///
+    /// Padding bytes within missing pieces aren't counted. A complete
+    /// download needs nothing.
+    pub fn remaining_disk_space(&self, have: &Bitfield) -> u64 {
+        let missing: u64 = (0..self.info.num_pieces())
+            .filter(|&i| !have.has(i))
+            .map(|i| match &self.info.files {
+                FileInfo::Single { .. } => {
+                    let (start, end) = self.info.piece_bounds(i);
+                    end - start
+                }
+                FileInfo::Multi { .. } => self
+                    .info
+                    .files_in_piece(i)
+                    .into_iter()
+                    .filter(|(file, _)| !file.is_padding())
+                    .map(|(_, range)| range.end - range.start)
+                    .sum(),
+            })
+            .sum();
+        if missing == 0 {
+            return 0;
+        }
+        missing + self.info.piece_length
+    }
+
+    /// Rough number of bytes this value occupies in memory, for budgeting
+    /// bulk parsing
+    ///
+    /// Counts the struct itself and the contents of its heap data (piece
+    /// hashes, the raw info dictionary, file paths, tracker URLs and text
+    /// fields) but not allocator overhead or spare capacity, so it is a lower
+    /// bound.
+    pub fn estimated_memory(&self) -> usize {
+        let text = |s: &Option<String>| s.as_ref().map_or(0, String::len);
+        let bytes = |b: &Option<Vec<u8>>| b.as_ref().map_or(0, Vec::len);
+
+        let files = match &self.info.files {
+            FileInfo::Single { .. } => 0,
+            FileInfo::Multi { files } => files
+                .iter()
+                .map(|f| {
+                    std::mem::size_of::<FileEntry>()
+                        + f.attributes.len()
+                        + f.path
+                            .iter()
+                            .map(|part| std::mem::size_of::<String>() + part.len())
+                            .sum::<usize>()
+                })
+                .sum(),
+        };
+        let trackers: usize = self
+            .announce_list
+            .iter()
+            .flatten()
+            .chain(&self.url_list)
+            .map(|url| std::mem::size_of::<String>() + url.len())
+            .sum();
+        let nodes: usize = self
+            .nodes
+            .iter()
+            .chain(&self.nodes6)
+            .map(|(host, _)| std::mem::size_of::<(String, u16)>() + host.len())
+            .sum();
+
+        std::mem::size_of::<Self>()
+            + self.info.pieces.len() * 20
+            + self.info.name.len()
+            + self.info.name_bytes.len()
+            + files
+            + self.announce.as_ref().map_or(0, String::len)
+            + trackers
+            + nodes
+            + text(&self.comment)
+            + bytes(&self.comment_bytes)
+            + text(&self.created_by)
+            + bytes(&self.created_by_bytes)
+            + bytes(&self.info_bytes)
+    }
+
+    /// Get number of pieces
+    pub fn num_pieces(&self) -> usize {
+        self.info.num_pieces()
+    }
+
+    /// Number of pieces the content spans; see [`Info::piece_count`]
+    pub fn piece_count(&self) -> u64 {
+        self.info.piece_count()
+    }
+
+    /// Global byte range of a piece; see [`Info::piece_bounds`]
+    pub fn piece_bounds(&self, index: usize) -> (u64, u64) {
+        self.info.piece_bounds(index)
+    }
+
+    /// Files a piece overlaps; see [`Info::files_in_piece`]
+    pub fn files_in_piece(&self, index: usize) -> Vec<(&FileEntry, Range<u64>)> {
+        self.info.files_in_piece(index)
+    }
+
+    /// A new torrent holding only the files at `indices` (as ordered by
+    /// [`Info::files`]), with its own info hash
+    ///
+    /// The original piece hashes are reused, so every run of consecutive
+    /// selected files must start on a piece boundary and end on one (or at
+    /// the end of the content, for the last run). Fails with
+    /// `Error::InvalidMetainfo` for selections that aren't aligned like
+    /// that, for out-of-range or duplicate indices, for an empty selection,
+    /// and when the piece hashes were skipped while parsing or their count
+    /// doesn't match [`Metainfo::piece_count`].
+    pub fn with_files(&self, indices: &[usize]) -> crate::Result<Metainfo> {
+        let FileInfo::Multi { files } = &self.info.files else {
+            return Err(invalid("only multi-file torrents can be filtered"));
+        };
+        if self.info.pieces.len() != self.info.num_pieces() {
+            return Err(invalid("piece hashes were not parsed"));
+        }
+        if self.info.piece_length == 0 {
+            return Err(invalid("piece length is zero"));
+        }
+        if self.info.num_pieces() as u64 != self.piece_count() {
+            return Err(invalid(format!(
+                "{} piece hashes for {} pieces of content",
+                self.info.num_pieces(),
+                self.piece_count()
+            )));
+        }
+        let mut selected = indices.to_vec();
+        selected.sort_unstable();
+        selected.dedup();
+        if selected.is_empty() || selected.len() != indices.len() {
+            return Err(invalid("file selection is empty or has duplicates"));
+        }
+        if selected.last().is_some_and(|&i| i >= files.len()) {
+            return Err(invalid("file index out of range"));
+        }
+
+        let piece_length = self.info.piece_length;
+        let total = self.total_size();
+        let mut offsets = Vec::with_capacity(files.len());
+        files.iter().fold(0u64, |offset, f| {
+            offsets.push(offset);
+            offset + f.length
+        });
+
+        // Group the selection into runs of consecutive files
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for &i in &selected {
+            match runs.last_mut() {
+                Some((_, end)) if *end == i => *end = i + 1,
+                _ => runs.push((i, i + 1)),
+            }
+        }
+
+        let mut pieces = Vec::new();
+        for (n, &(first, end)) in runs.iter().enumerate() {
+            let start = offsets[first];
+            let stop = offsets[end - 1] + files[end - 1].length;
+            let is_last = n + 1 == runs.len();
+            let aligned_end = stop % piece_length == 0 || (is_last && stop == total);
+            if start % piece_length != 0 || !aligned_end {
+                return Err(invalid(format!(
+                    "files {}..{} are not aligned to piece boundaries",
+                    first, end
+                )));
+            }
+            let piece_range = (start / piece_length) as usize..stop.div_ceil(piece_length) as usize;
+            pieces.extend_from_slice(&self.info.pieces[piece_range]);
+        }
+
+        let mut subset = self.clone();
+        subset.info.files = FileInfo::Multi {
+            files: selected.iter().map(|&i| files[i].clone()).collect(),
+        };
+        subset.info.pieces = pieces;
+
+        let mut info = subset.info.to_bencode();
+        info.meta_version = self.meta_version;
+        let info_bytes = serde_bencode::to_bytes(&info)
+            .map_err(|e| crate::Error::BencodeEncode(e.to_string()))?;
+        subset.info_hash = Sha1::digest(&info_bytes).into();
+        subset.info_bytes = Some(info_bytes);
+        Ok(subset)
+    }
+
+    /// Number of files in the torrent; a single-file torrent has one
+    pub fn file_count(&self) -> usize {
+        match &self.info.files {
+            FileInfo::Single { .. } => 1,
+            FileInfo::Multi { files } => files.len(),
+        }
+    }
+
+    /// Whether the torrent is private (BEP 27), so DHT and peer exchange
+    /// must not be used for it
+    pub fn is_private(&self) -> bool {
+        self.info.private
+    }
+
+    /// Whether `info.name` names a directory holding the files (multi-file
+    /// torrents) rather than the single file itself
+    pub fn name_is_directory(&self) -> bool {
+        matches!(self.info.files, FileInfo::Multi { .. })
+    }
+
+    /// Short identifier for logs and UIs: the first 8 hex characters of the
+    /// info hash, like a git short hash
+    pub fn short_id(&self) -> String {
+        hex::encode(&self.info_hash[..4])
+    }
+
+    /// SHA-1 over the sorted `(path, size)` pairs of the content
+    ///
+    /// Ignores piece length, piece hashes and trackers, so the same files
+    /// packaged as different torrents share a layout hash. Matching hashes
+    /// mean likely-identical content, not proof of it.
+    pub fn layout_hash(&self) -> [u8; 20] {
+        self.info.layout_hash()
+    }
+
+    /// Get all tracker URLs (primary + backups)
+    ///
+    /// Empty for trackerless torrents.
+    pub fn all_trackers(&self) -> Vec<String> {
+        let mut trackers: Vec<String> = self.announce.iter().cloned().collect();
+        for tier in &self.announce_list {
+            trackers.extend(tier.clone());
+        }
+        trackers.sort();
+        trackers.dedup();
+        trackers
+    }
+
+    /// Up to `per_tier` trackers from each tier, tiers in order
+    ///
+    /// Announcing to this subset still reaches every tier. Without an
+    /// `announce-list`, the primary tracker forms the only tier. A URL
+    /// repeated in a later tier is only taken once.
+    pub fn announce_set(&self, per_tier: usize) -> Vec<String> {
+        let primary = [self.announce.iter().cloned().collect()];
+        let tiers = if self.announce_list.is_empty() {
+            &primary[..]
+        } else {
+            &self.announce_list[..]
+        };
+
+        let mut selected: Vec<String> = Vec::new();
+        for tier in tiers {
+            let fresh: Vec<&String> = tier
+                .iter()
+                .filter(|url| !selected.contains(url))
+                .take(per_tier)
+                .collect();
+            selected.extend(fresh.into_iter().cloned());
+        }
+        selected
+    }
+
+    /// Shuffle the trackers within each `announce-list` tier, as BEP 12
+    /// asks clients to do on load
+    ///
+    /// Tier order is kept and tiers are never merged. Parsing leaves the
+    /// list as written, so this is opt-in; pass a seeded RNG for a
+    /// reproducible order.
+    pub fn shuffle_trackers<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        for tier in &mut self.announce_list {
+            tier.shuffle(rng);
+        }
+    }
+
+    /// Tool name and version from `created by`
+    ///
+    /// Understands the usual `name version`, `name vversion` and
+    /// `name/version` forms, ignoring anything after the version (such as
+    /// `Transmission/2.94 (d00c8d9)`). A bare name gives no version. Other
+    /// text is not guessed at and gives `None`.
+    pub fn creation_tool(&self) -> Option<(String, Option<String>)> {
+        let created_by = self.created_by.as_deref()?.trim();
+        let (name, rest) = created_by
+            .split_once([' ', '/'])
+            .unwrap_or((created_by, ""));
+        if name.is_empty() {
+            return None;
+        }
+        let rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some((name.to_string(), None));
+        }
+
+        let version = rest
+            .strip_prefix(['v', 'V'])
+            .unwrap_or(rest)
+            .split([' ', '('])
+            .next()
+            .filter(|v| v.starts_with(|c: char| c.is_ascii_digit()))?;
+        Some((name.to_string(), Some(version.to_string())))
+    }
+
+    /// Web seed URLs to fetch content from over HTTP (BEP 19)
+    ///
+    /// Drops empty entries (a common placeholder) and duplicates, keeping
+    /// listed order.
+    pub fn web_seeds(&self) -> Vec<&str> {
+        let mut seen = std::collections::HashSet::new();
+        self.url_list
+            .iter()
+            .map(|url| url.trim())
+            .filter(|url| !url.is_empty() && seen.insert(*url))
+            .collect()
+    }
+
+    /// DHT nodes exactly as listed, `nodes` then `nodes6`
+    ///
+    /// Trackerless torrents rely on these to find peers; see
+    /// [`Metainfo::dht_bootstrap_nodes`] for a cleaned-up list.
+    pub fn dht_nodes(&self) -> Vec<(String, u16)> {
+        self.nodes.iter().chain(&self.nodes6).cloned().collect()
+    }
+
+    /// DHT nodes ready to seed a routing table, IPv4 `nodes` before IPv6
+    /// `nodes6`
+    ///
+    /// Drops duplicates and entries with an empty host or a zero port,
+    /// keeping the first occurrence of each node in listed order. Private
+    /// torrents must not use the DHT, so they get no nodes at all.
+    pub fn dht_bootstrap_nodes(&self) -> Vec<(String, u16)> {
+        if self.is_private() {
+            return Vec::new();
+        }
+        let mut seen = std::collections::HashSet::new();
+        self.nodes
+            .iter()
+            .chain(&self.nodes6)
+            .filter(|(host, port)| {
+                let valid = !host.trim().is_empty() && *port != 0;
+                if !valid {
+                    tracing::trace!("Skipping invalid DHT node {}:{}", host, port);
+                }
+                valid
+            })
+            .filter(|node| seen.insert((*node).clone()))
+            .cloned()
+            .collect()
+    }
+
+    /// Probe every tracker in parallel, returning each URL with whether it
+    /// answered within `timeout`
+    ///
+    /// Results follow [`Metainfo::all_trackers`] order. At most eight
+    /// trackers are probed at a time, so huge tracker lists don't spawn a
+    /// thread each.
+    pub fn probe_trackers(&self, timeout: Duration) -> Vec<(String, bool)> {
+        let trackers = self.all_trackers();
+        let next = AtomicUsize::new(0);
+        let mut alive = vec![false; trackers.len()];
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..trackers.len().min(MAX_TRACKER_PROBES))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut probed = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(url) = trackers.get(index) else {
+                                return probed;
+                            };
+                            let answered = crate::Tracker::new(url.clone()).is_alive(timeout);
+                            probed.push((index, answered));
+                        }
+                    })
+                })
+                .collect();
+
+            for worker in workers {
+                for (index, answered) in worker.join().expect("tracker probe panicked") {
+                    alive[index] = answered;
+                }
+            }
+        });
+
+        trackers.into_iter().zip(alive).collect()
+    }
+}
+
+impl Info {
+    /// Convert a decoded info dictionary, optionally skipping the piece hashes
+    fn from_value(value: &Value, skip_pieces: bool) -> crate::Result<Self> {
+        let info = as_dict(value, "info")?;
+
+        // Parse piece hashes. Some encoders emit a list of 20-byte strings
+        // instead of one concatenated string, so accept both.
+        let pieces = match require(info, "pieces")? {
+            Value::List(hashes) => hashes
+                .iter()
+                .map(|hash| match hash {
+                    Value::Bytes(bytes) if bytes.len() == 20 => Ok(bytes.as_slice()),
+                    _ => Err(invalid("`pieces` list entries must be 20-byte strings")),
+                })
+                .collect::<crate::Result<Vec<_>>>()?
+                .concat()
+                .into(),
+            value => Cow::Borrowed(as_bytes(value, "pieces")?),
+        };
+        if !pieces.len().is_multiple_of(20) {
+            return Err(crate::Error::InvalidMetainfo(
+                "Pieces length must be multiple of 20".to_string(),
+            ));
+        }
+        let num_pieces = pieces.len() / 20;
+        let pieces: Vec<[u8; 20]> = if skip_pieces {
+            Vec::new()
+        } else {
+            pieces
+                .chunks_exact(20)
+                .map(|chunk| {
+                    let mut hash = [0u8; 20];
+                    hash.copy_from_slice(chunk);
+                    hash
+                })
+                .collect()
+        };
+
+        // Convert file info
+        let files = if let Some(length) = info.get("length") {
+            // Some non-standard encoders split one file into parts this way.
+            // There are no part names to turn it into a multi-file layout.
+            if let Value::List(parts) = length {
+                return Err(invalid(format!(
+                    "`length` is a list of {} part sizes (split file), which is not \
+                     valid BitTorrent metainfo; expected a single integer",
+                    parts.len()
+                )));
+            }
+            FileInfo::Single {
+                length: as_u64(length, "length")?,
+            }
+        } else if let Some(files) = info.get("files") {
+            FileInfo::Multi {
+                files: as_list(files, "files")?
+                    .iter()
+                    .map(|file| {
+                        let file = as_dict(file, "files")?;
+                        Ok(FileEntry {
+                            path: as_list(require(file, "path")?, "path")?
+                                .iter()
+                                .map(|component| as_string(component, "path"))
+                                .collect::<crate::Result<_>>()?,
+                            length: as_u64(require(file, "length")?, "length")?,
+                            attributes: match file.get("attr") {
+                                Some(attr) => as_string(attr, "attr")?,
+                                None => String::new(),
+                            },
+                        })
+                    })
+                    .collect::<crate::Result<_>>()?,
+            }
+        } else {
+            return Err(invalid("info dictionary has neither `length` nor `files`"));
+        };
+
+        // Two entries writing to the same file would overwrite each other
+        if let FileInfo::Multi { files } = &files {
+            let mut seen = std::collections::HashSet::new();
+            for file in files {
+                file.path.iter().try_for_each(|c| check_path_component(c))?;
+                let normalized: Vec<&str> = file
+                    .path
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|c| !c.is_empty() && *c != ".")
+                    .collect();
+                if !seen.insert(normalized) {
+                    return Err(invalid("duplicate file path"));
+                }
+            }
+        }
+
+        // Older clients wrote names in the system code page
+        let name_bytes = as_bytes(require(info, "name")?, "name")?.to_vec();
+        check_path_component(&String::from_utf8_lossy(&name_bytes))?;
+
+        Ok(Info {
+            name: String::from_utf8_lossy(&name_bytes).into_owned(),
+            name_bytes,
+            piece_length: as_u64(require(info, "piece length")?, "piece length")?,
+            pieces,
+            skipped_pieces: skip_pieces.then_some(num_pieces),
+            files,
+            // Fail safe: any value other than an explicit 0 keeps the torrent
+            // off DHT and PEX
+            private: info
+                .get("private")
+                .is_some_and(|value| value.as_int() != Some(0)),
+        })
+    }
+
+    /// Convert back into the bencode info dictionary layout
+    fn to_bencode(&self) -> BencodeInfo {
+        let file_info = match &self.files {
+            FileInfo::Single { length } => BencodeFileInfo::Single { length: *length },
+            FileInfo::Multi { files } => BencodeFileInfo::Multi {
+                files: files
+                    .iter()
+                    .map(|f| BencodeFile {
+                        path: f.path.clone(),
+                        length: f.length,
+                        attr: (!f.attributes.is_empty()).then(|| f.attributes.clone()),
+                    })
+                    .collect(),
+            },
+        };
+
+        BencodeInfo {
+            name: self.name_bytes.clone(),
+            piece_length: self.piece_length,
+            pieces: self.pieces.concat(),
+            private: self.private.then_some(1),
+            meta_version: None,
+            file_info,
+        }
+    }
+}
+
+/// Parse a `[host, port]` entry from the `nodes` list, skipping anything malformed
+fn parse_node(node: &Value) -> Option<(String, u16)> {
+    let parsed = match node {
+        Value::List(pair) => match pair.as_slice() {
+            [Value::Bytes(host), Value::Int(port)] => String::from_utf8(host.clone())
+                .ok()
+                .zip(u16::try_from(*port).ok()),
+            _ => None,
+        },
+        _ => None,
+    };
+    if parsed.is_none() {
+        tracing::trace!("Skipping malformed DHT node entry {:?}", node);
+    }
+    parsed
+}
+
+/// Parse a `url-list` entry, skipping anything that is not a string
+fn parse_web_seed(url: &Value) -> Option<String> {
+    let parsed = url
+        .as_bytes()
+        .map(|b| String::from_utf8_lossy(b).into_owned());
+    if parsed.is_none() {
+        tracing::trace!("Skipping malformed web seed entry {:?}", url);
+    }
+    parsed
+}
+
+/// Reject a `name` or `path` component that could leave the download
+/// directory once joined onto it: `..`, absolute paths and anything with a
+/// path separator. Empty and `.` components are harmless and allowed.
+fn check_path_component(component: &str) -> crate::Result<()> {
+    let mut parts = Path::new(component).components();
+    let single = matches!(
+        (parts.next(), parts.next()),
+        (Some(Component::Normal(_) | Component::CurDir) | None, None)
+    );
+    if !single || component.contains(['/', '\\']) {
+        return Err(invalid(format!(
+            "unsafe file path component {:?}",
+            component
+        )));
+    }
+    Ok(())
+}
+
+fn invalid(message: impl Into<String>) -> crate::Error {
+    crate::Error::InvalidMetainfo(message.into())
+}
+
+fn require<'a>(dict: &'a BTreeMap<String, Value>, key: &str) -> crate::Result<&'a Value> {
+    dict.get(key)
+        .ok_or_else(|| invalid(format!("missing field `{}`", key)))
+}
+
+fn as_dict<'a>(value: &'a Value, key: &str) -> crate::Result<&'a BTreeMap<String, Value>> {
+    value
+        .as_dict()
+        .ok_or_else(|| invalid(format!("`{}` must be a dictionary", key)))
+}
+
+fn as_list<'a>(value: &'a Value, key: &str) -> crate::Result<&'a [Value]> {
+    value
+        .as_list()
+        .ok_or_else(|| invalid(format!("`{}` must be a list", key)))
+}
+
+fn as_bytes<'a>(value: &'a Value, key: &str) -> crate::Result<&'a [u8]> {
+    value
+        .as_bytes()
+        .ok_or_else(|| invalid(format!("`{}` must be a string", key)))
+}
+
+fn as_string(value: &Value, key: &str) -> crate::Result<String> {
+    String::from_utf8(as_bytes(value, key)?.to_vec())
+        .map_err(|_| invalid(format!("`{}` must be valid UTF-8", key)))
+}
+
+fn as_int(value: &Value, key: &str) -> crate::Result<i64> {
+    value
+        .as_int()
+        .ok_or_else(|| invalid(format!("`{}` must be an integer", key)))
+}
+
+fn as_u64(value: &Value, key: &str) -> crate::Result<u64> {
+    u64::try_from(as_int(value, key)?)
+        .map_err(|_| invalid(format!("`{}` must not be negative", key)))
+}
+
+/// Bytes to store for a text field: the original bytes while `text` is still
+/// their decoding, otherwise the (edited) text itself
+fn stored_text(text: &Option<String>, bytes: &Option<Vec<u8>>) -> Option<Vec<u8>> {
+    match (text, bytes) {
+        (Some(text), Some(bytes)) if String::from_utf8_lossy(bytes) == text.as_str() => {
+            Some(bytes.clone())
+        }
+        (text, _) => text.clone().map(String::into_bytes),
+    }
+}
+
+/// SHA-1 of the bencoded info dictionary
+fn hash_info(info: &BencodeInfo) -> crate::Result<[u8; 20]> {
+    let info_bytes =
+        serde_bencode::to_bytes(info).map_err(|e| crate::Error::InvalidMetainfo(e.to_string()))?;
+    let mut hasher = Sha1::new();
+    hasher.update(&info_bytes);
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_torrent() -> Vec<u8> {
+        // Minimal valid .torrent file
+        let torrent = r#"d8:announce9:localhost4:infod6:lengthi1000e4:name8:test.txt12:piece lengthi512e6:pieces20:12345678901234567890ee"#;
+        torrent.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_parse_torrent() {
+        let data = create_test_torrent();
+        let metainfo = Metainfo::from_bytes(&data).unwrap();
+
+        assert_eq!(metainfo.announce.as_deref(), Some("localhost"));
+        assert_eq!(metainfo.info.name, "test.txt");
+        assert_eq!(metainfo.total_size(), 1000);
+        assert_eq!(metainfo.num_pieces(), 1);
+    }
+
+    #[test]
+    fn test_info_hash_consistency() {
+        let data = create_test_torrent();
+        let metainfo1 = Metainfo::from_bytes(&data).unwrap();
+        let metainfo2 = Metainfo::from_bytes(&data).unwrap();
+
+        assert_eq!(metainfo1.info_hash, metainfo2.info_hash);
+    }
+
+    #[test]
+    fn test_from_bytes_verify() {
+        let data = create_test_torrent();
+        let expected = Metainfo::from_bytes(&data).unwrap().info_hash;
+
+        let metainfo = Metainfo::from_bytes_verify(&data, expected).unwrap();
+        assert_eq!(metainfo.info_hash, expected);
+
+        let result = Metainfo::from_bytes_verify(&data, [0u8; 20]);
+        assert!(
+            matches!(result, Err(crate::Error::InvalidMetainfo(msg)) if msg == "info hash mismatch")
+        );
+    }
+
+    #[test]
+    fn test_short_id() {
+        let metainfo = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        let short_id = metainfo.short_id();
+
+        assert_eq!(short_id.len(), 8);
+        assert!(hex::encode(metainfo.info_hash).starts_with(&short_id));
+    }
+
+    #[test]
+    fn test_layout_hash_ignores_piece_length() {
+        let torrent = |piece_length: u32, pieces: &str| {
+            format!(
+                "d8:announce9:localhost4:infod5:filesl\
+                 d6:lengthi1000e4:pathl1:aee\
+                 d6:lengthi24e4:pathl1:beee\
+                 4:name3:dir12:piece lengthi{}e6:pieces{}:{}ee",
+                piece_length,
+                pieces.len(),
+                pieces
+            )
+        };
+        let small = Metainfo::from_bytes(torrent(512, &"x".repeat(40)).as_bytes()).unwrap();
+        let large = Metainfo::from_bytes(torrent(1024, &"y".repeat(20)).as_bytes()).unwrap();
+
+        assert_ne!(small.info_hash, large.info_hash);
+        assert_eq!(small.layout_hash(), large.layout_hash());
+
+        let other = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        assert_ne!(small.layout_hash(), other.layout_hash());
+    }
+
+    #[test]
+    fn test_length_list_rejected_clearly() {
+        let torrent = b"d8:announce9:localhost4:infod6:lengthli500ei500ee4:name4:test\
+                        12:piece lengthi512e6:pieces40:1234567890123456789012345678901234567890ee";
+
+        match Metainfo::from_bytes(torrent) {
+            Err(crate::Error::InvalidMetainfo(message)) => {
+                assert!(message.contains("split file"), "{}", message)
+            }
+            other => panic!("Expected InvalidMetainfo, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_info_hash_uses_literal_info_bytes() {
+        // Keys out of order plus one this crate doesn't model
+        let info = "d4:name4:test6:lengthi1000e12:piece lengthi512e\
+                    6:pieces20:123456789012345678906:source3:abce";
+        let torrent = format!("d8:announce9:localhost4:info{}e", info);
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        assert_eq!(
+            Metainfo::raw_info(torrent.as_bytes()).unwrap(),
+            info.as_bytes()
+        );
+        let expected: [u8; 20] = Sha1::digest(info.as_bytes()).into();
+        assert_eq!(metainfo.info_hash, expected);
+        assert_ne!(hash_info(&metainfo.info.to_bencode()).unwrap(), expected);
+
+        assert!(Metainfo::raw_info(b"d8:announce9:localhoste").is_err());
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut data = create_test_torrent();
+        let expected = Metainfo::from_bytes(&data).unwrap();
+        data.extend_from_slice(b"\x00\x00garbaged4:spami1ee");
+
+        assert!(matches!(
+            Metainfo::from_bytes(&data),
+            Err(crate::Error::InvalidMetainfo(_))
+        ));
+
+        let options = ParseOptions {
+            allow_trailing_bytes: true,
+        };
+        let metainfo = Metainfo::from_bytes_with_options(&data, &options).unwrap();
+        assert_eq!(metainfo.info_hash, expected.info_hash);
+        assert_eq!(metainfo.total_size(), expected.total_size());
+    }
+
+    #[test]
+    fn test_invalid_piece_length() {
+        // Pieces must be multiple of 20
+        let torrent_data = b"d8:announce9:localhost4:infod6:lengthi1000e4:name4:test12:piece lengthi512e6:pieces19:1234567890123456789ee";
+
+        let result = Metainfo::from_bytes(torrent_data);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_piece_count() {
+        // 1000 bytes at 512 per piece needs two hashes, the test torrent has one
+        let short = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        assert!(matches!(
+            short.validate(),
+            Err(crate::Error::InvalidMetainfo(_))
+        ));
+
+        let exact = "d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                     12:piece lengthi512e6:pieces40:1234567890123456789012345678901234567890ee";
+        assert!(
+            Metainfo::from_bytes(exact.as_bytes())
+                .unwrap()
+                .validate()
+                .is_ok()
+        );
+        let metadata_only = Metainfo::from_bytes_metadata_only(exact.as_bytes()).unwrap();
+        assert!(metadata_only.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_piece_length_limit() {
+        let torrent = |piece_length: u64| {
+            format!(
+                "d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                 12:piece lengthi{}e6:pieces20:12345678901234567890ee",
+                piece_length
+            )
+        };
+        let validate = |piece_length| {
+            Metainfo::from_bytes(torrent(piece_length).as_bytes())
+                .unwrap()
+                .validate()
+        };
+
+        assert!(validate(MAX_PIECE_LENGTH).is_ok());
+        assert!(matches!(
+            validate(MAX_PIECE_LENGTH * 2),
+            Err(crate::Error::InvalidMetainfo(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_multi_file_piece_count() {
+        // 300 + 300 bytes at 512 per piece needs two hashes
+        let torrent = |pieces: &str| {
+            format!(
+                "d8:announce9:localhost4:infod5:filesl\
+                 d6:lengthi300e4:pathl1:aee\
+                 d6:lengthi300e4:pathl1:beee\
+                 4:name3:dir12:piece lengthi512e6:pieces{}:{}ee",
+                pieces.len(),
+                pieces
+            )
+        };
+        let parse = |pieces: &str| Metainfo::from_bytes(torrent(pieces).as_bytes()).unwrap();
+
+        assert!(parse(&"x".repeat(40)).validate().is_ok());
+        for wrong in ["x".repeat(20), "x".repeat(60)] {
+            assert!(matches!(
+                parse(&wrong).validate(),
+                Err(crate::Error::InvalidMetainfo(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_required_disk_space() {
+        // 1000 bytes in pieces of 512 and 488
+        let torrent = format!(
+            "d8:announce9:localhost4:infod6:lengthi1000e4:name8:test.txt\
+             12:piece lengthi512e6:pieces40:{}ee",
+            "x".repeat(40)
+        );
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.required_disk_space(), 1000 + 512);
+
+        let mut have = Bitfield::new(2);
+        assert_eq!(metainfo.remaining_disk_space(&have), 1000 + 512);
+        have.set(0);
+        assert_eq!(metainfo.remaining_disk_space(&have), 488 + 512);
+        have.set(1);
+        assert_eq!(metainfo.remaining_disk_space(&have), 0);
+    }
+
+    #[test]
+    fn test_required_disk_space_skips_padding() {
+        // Pieces of 512: `a` and its padding, then `b` across two pieces
+        let torrent = format!(
+            "d8:announce9:localhost4:infod5:filesl\
+             d6:lengthi300e4:pathl1:aee\
+             d4:attr1:p6:lengthi212e4:pathl4:.pad3:212ee\
+             d6:lengthi700e4:pathl1:bee\
+             e4:name3:dir12:piece lengthi512e6:pieces60:{}ee",
+            "x".repeat(60)
+        );
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.required_disk_space(), 1000 + 512);
+
+        let mut have = Bitfield::new(3);
+        assert_eq!(metainfo.remaining_disk_space(&have), 1000 + 512);
+        have.set(1);
+        assert_eq!(metainfo.remaining_disk_space(&have), 300 + 188 + 512);
+        have.set(2);
+        assert_eq!(metainfo.remaining_disk_space(&have), 300 + 512);
+        have.set(0);
+        assert_eq!(metainfo.remaining_disk_space(&have), 0);
+    }
+
+    #[test]
+    fn test_from_data_uri() {
+        let torrent = create_test_torrent();
+        let payload = BASE64.encode(&torrent);
+        let expected = Metainfo::from_bytes(&torrent).unwrap();
+
+        let uri = format!("data:application/x-bittorrent;base64,{}", payload);
+        assert_eq!(
+            Metainfo::from_data_uri(&uri).unwrap().info_hash,
+            expected.info_hash
+        );
+
+        // Case-insensitive, extra parameters, escaped and wrapped payload
+        let uri = format!(
+            "DATA:Application/X-BitTorrent;name=test;base64,{}\n{}",
+            &payload[..8],
+            payload[8..].replace('=', "%3D")
+        );
+        assert_eq!(
+            Metainfo::from_data_uri(&uri).unwrap().info_hash,
+            expected.info_hash
+        );
+
+        for uri in [
+            format!("data:text/plain;base64,{}", payload),
+            format!("data:;base64,{}", payload),
+            format!("data:application/x-bittorrent,{}", payload),
+            "data:application/x-bittorrent;base64".to_string(),
+            "data:application/x-bittorrent;base64,not*base64".to_string(),
+            "data:application/x-bittorrent;base64,".to_string(),
+            format!("http://example.com/{}", payload),
+        ] {
+            assert!(
+                matches!(
+                    Metainfo::from_data_uri(&uri),
+                    Err(crate::Error::InvalidMetainfo(_))
+                ),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimated_memory_scales_with_pieces() {
+        let metainfo = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        let base = metainfo.estimated_memory();
+        assert!(base > std::mem::size_of::<Metainfo>());
+
+        let mut bigger = metainfo.clone();
+        bigger.info.pieces.extend(vec![[0u8; 20]; 1000]);
+        assert_eq!(bigger.estimated_memory(), base + 1000 * 20);
+    }
+
+    #[test]
+    fn test_with_files_aligned() {
+        // 4-byte pieces over files of 8, 4 and 5 bytes: every file starts
+        // on a piece boundary
+        let pieces: String = ('a'..='e').map(|c| c.to_string().repeat(20)).collect();
+        let torrent = format!(
+            "d8:announce9:localhost4:infod5:filesl\
+             d6:lengthi8e4:pathl1:aee\
+             d6:lengthi4e4:pathl1:bee\
+             d6:lengthi5e4:pathl1:ceee\
+             4:name3:dir12:piece lengthi4e6:pieces100:{}ee",
+            pieces
+        );
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        let subset = metainfo.with_files(&[2, 0]).unwrap();
+        assert_eq!(subset.file_count(), 2);
+        assert_eq!(subset.total_size(), 13);
+        assert_eq!(subset.info.pieces, {
+            let p = &metainfo.info.pieces;
+            vec![p[0], p[1], p[3], p[4]]
+        });
+        assert!(subset.validate().is_ok());
+        assert_ne!(subset.info_hash, metainfo.info_hash);
+        let reparsed = Metainfo::from_bytes(&subset.to_bytes().unwrap()).unwrap();
+        assert_eq!(reparsed.info_hash, subset.info_hash);
+
+        let middle = metainfo.with_files(&[1]).unwrap();
+        assert_eq!(middle.info.pieces, vec![metainfo.info.pieces[2]]);
+
+        for indices in [&[][..], &[3], &[1, 1]] {
+            assert!(metainfo.with_files(indices).is_err(), "{:?}", indices);
+        }
+    }
+
+    #[test]
+    fn test_with_files_rejects_unaligned() {
+        let torrent = "d8:announce9:localhost4:infod5:filesl\
+                       d6:lengthi6e4:pathl1:aee\
+                       d6:lengthi4e4:pathl1:beee\
+                       4:name3:dir12:piece lengthi4e\
+                       6:pieces60:123456789012345678901234567890123456789012345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        assert!(matches!(
+            metainfo.with_files(&[0]),
+            Err(crate::Error::InvalidMetainfo(_))
+        ));
+        assert!(metainfo.with_files(&[1]).is_err());
+        assert!(metainfo.with_files(&[0, 1]).is_ok());
+    }
+
+    #[test]
+    fn test_with_files_too_few_hashes() {
+        // Two 100-byte pieces of content, but only one hash
+        let torrent = "d8:announce9:localhost4:infod5:filesl\
+                       d6:lengthi100e4:pathl1:aee\
+                       d6:lengthi100e4:pathl1:beee\
+                       4:name3:dir12:piece lengthi100e\
+                       6:pieces20:12345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        for indices in [&[0][..], &[1], &[0, 1]] {
+            assert!(matches!(
+                metainfo.with_files(indices),
+                Err(crate::Error::InvalidMetainfo(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_piece_calculation() {
+        let data = create_test_torrent();
+        let metainfo = Metainfo::from_bytes(&data).unwrap();
+
+        // 1000 bytes / 512 piece_length = 2 pieces (rounded up)
+        assert_eq!(metainfo.num_pieces(), 1);
+    }
+
+    #[test]
+    fn test_piece_layout_wrappers() {
+        let metainfo = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+
+        // Counted from the content size, though the test torrent only lists
+        // one hash
+        assert_eq!(metainfo.piece_count(), 2);
+        assert_eq!(metainfo.piece_bounds(1), (512, 1000));
+        assert!(metainfo.files_in_piece(0).is_empty());
+    }
+
+    #[test]
+    fn test_file_attributes() {
+        let data = "d8:announce9:localhost4:infod5:filesl\
+                    d4:attr1:x6:lengthi100e4:pathl3:runee\
+                    d4:attr1:p6:lengthi412e4:pathl4:.pad3:412ee\
+                    d6:lengthi50e4:pathl8:data.binee\
+                    e4:name3:dir12:piece lengthi512e\
+                    6:pieces40:1234567890123456789012345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(data.as_bytes()).unwrap();
+
+        let FileInfo::Multi { files } = &metainfo.info.files else {
+            panic!("expected a multi-file torrent");
+        };
+        assert!(files[0].is_executable() && !files[0].is_padding());
+        assert!(files[1].is_padding() && !files[1].is_executable());
+        assert_eq!(files[2].attributes, "");
+        assert!(!files[2].is_padding());
+
+        // Attributes are part of the info dictionary, so they must survive
+        // re-encoding for the info hash to match
+        assert_eq!(
+            hash_info(&metainfo.info.to_bencode()).unwrap(),
+            metainfo.info_hash
+        );
+    }
+
+    #[test]
+    fn test_file_count() {
+        let single = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        assert_eq!(single.file_count(), 1);
+        assert!(!single.name_is_directory());
+
+        let multi = "d8:announce9:localhost4:infod5:filesld6:lengthi10e4:pathl1:aeed6:lengthi20e4:pathl1:beed6:lengthi30e4:pathl1:ceee4:name3:dir12:piece lengthi512e6:pieces20:12345678901234567890ee";
+        let multi = Metainfo::from_bytes(multi.as_bytes()).unwrap();
+        assert_eq!(multi.file_count(), 3);
+        assert!(multi.name_is_directory());
+    }
+
+    /// The original parse path: deserialize through serde, then hash the
+    /// re-encoded info dictionary
+    fn from_bytes_reencoded(bytes: &[u8]) -> Metainfo {
+        #[derive(Deserialize)]
+        struct LegacyTorrent {
+            announce: String,
+            #[serde(rename = "announce-list", default)]
+            announce_list: Vec<Vec<String>>,
+            info: BencodeInfo,
+            #[serde(rename = "creation date")]
+            creation_date: Option<i64>,
+            comment: Option<String>,
+            #[serde(rename = "created by")]
+            created_by: Option<String>,
+        }
+
+        let torrent: LegacyTorrent = serde_bencode::from_bytes(bytes).unwrap();
+        let info_hash = hash_info(&torrent.info).unwrap();
+        let info_bytes = serde_bencode::to_bytes(&torrent.info).unwrap();
+        let files = match torrent.info.file_info {
+            BencodeFileInfo::Single { length } => FileInfo::Single { length },
+            BencodeFileInfo::Multi { files } => FileInfo::Multi {
+                files: files
+                    .into_iter()
+                    .map(|f| FileEntry {
+                        path: f.path,
+                        length: f.length,
+                        attributes: f.attr.unwrap_or_default(),
+                    })
+                    .collect(),
+            },
+        };
+
+        Metainfo {
+            announce: Some(torrent.announce),
+            announce_list: torrent.announce_list,
+            info_hash,
+            info: Info {
+                name: String::from_utf8_lossy(&torrent.info.name).into_owned(),
+                name_bytes: torrent.info.name,
+                piece_length: torrent.info.piece_length,
+                pieces: torrent
+                    .info
+                    .pieces
+                    .chunks_exact(20)
+                    .map(|c| c.try_into().unwrap())
+                    .collect(),
+                skipped_pieces: None,
+                files,
+                private: torrent.info.private == Some(1),
+            },
+            info_bytes: Some(info_bytes),
+            creation_date: torrent.creation_date,
+            comment_bytes: torrent.comment.clone().map(String::into_bytes),
+            comment: torrent.comment,
+            created_by_bytes: torrent.created_by.clone().map(String::into_bytes),
+            created_by: torrent.created_by,
+            nodes: Vec::new(),
+            nodes6: Vec::new(),
+            url_list: Vec::new(),
+            meta_version: None,
+        }
+    }
+
+    #[test]
+    fn test_single_pass_matches_reencoded_parse() {
+        let multi = "d8:announce9:tracker-113:announce-listll9:tracker-1el9:tracker-2ee\
+                     7:comment5:hello10:created by4:test13:creation datei1234567890e\
+                     4:infod5:filesld6:lengthi1000e4:pathl3:dir5:file1eed6:lengthi24e\
+                     4:pathl5:file2eee4:name7:testdir12:piece lengthi512e\
+                     6:pieces40:1234567890123456789012345678901234567890ee";
+
+        for data in [create_test_torrent(), multi.as_bytes().to_vec()] {
+            let single_pass = Metainfo::from_bytes(&data).unwrap();
+            let reencoded = from_bytes_reencoded(&data);
+            assert_eq!(format!("{:?}", single_pass), format!("{:?}", reencoded));
+        }
+    }
+
+    #[test]
+    fn test_to_bytes_round_trip() {
+        let multi = "d8:announce9:tracker-113:announce-listll9:tracker-1el9:tracker-2ee\
+                     7:comment5:hello10:created by4:test13:creation datei1234567890e\
+                     4:infod5:filesld6:lengthi1000e4:pathl3:dir5:file1eed6:lengthi24e\
+                     4:pathl5:file2eee4:name7:testdir12:piece lengthi512e\
+                     6:pieces40:1234567890123456789012345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(multi.as_bytes()).unwrap();
+
+        // Already canonical, so it comes back byte for byte
+        assert_eq!(metainfo.to_bytes().unwrap(), multi.as_bytes());
+
+        let mut edited = metainfo.clone();
+        edited.comment = Some("edited".to_string());
+        let reparsed = Metainfo::from_bytes(&edited.to_bytes().unwrap()).unwrap();
+        assert_eq!(reparsed.info_hash, metainfo.info_hash);
+        assert_eq!(reparsed.comment.as_deref(), Some("edited"));
+        assert_eq!(reparsed.announce_list, metainfo.announce_list);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out.torrent");
+        edited.to_file(&path).unwrap();
+        assert_eq!(
+            Metainfo::from_file(&path).unwrap().info_hash,
+            metainfo.info_hash
+        );
+    }
+
+    #[test]
+    fn test_private_flag() {
+        let private = "d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                       12:piece lengthi512e6:pieces40:1234567890123456789012345678901234567890\
+                       7:privatei1eee";
+        let metainfo = Metainfo::from_bytes(private.as_bytes()).unwrap();
+        assert!(metainfo.is_private());
+
+        // Re-encoding keeps the flag, and with it the info hash
+        let reparsed = Metainfo::from_bytes(&metainfo.to_bytes().unwrap()).unwrap();
+        assert!(reparsed.is_private());
+        assert_eq!(reparsed.info_hash, metainfo.info_hash);
+
+        let public = private.replace("7:privatei1e", "7:privatei0e");
+        assert!(
+            !Metainfo::from_bytes(public.as_bytes())
+                .unwrap()
+                .is_private()
+        );
+        // A malformed flag still marks the torrent private
+        for flag in ["7:private1:1", "7:privatei2e"] {
+            let odd = private.replace("7:privatei1e", flag);
+            assert!(Metainfo::from_bytes(odd.as_bytes()).unwrap().is_private());
+        }
+        assert!(
+            !Metainfo::from_bytes(&create_test_torrent())
+                .unwrap()
+                .is_private()
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_keeps_unmodelled_info_keys() {
+        // `source` isn't modelled, but the original bytes are written back
+        let torrent = "d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                       12:piece lengthi512e6:pieces40:\
+                       12345678901234567890123456789012345678906:source3:abcee";
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.to_bytes().unwrap(), torrent.as_bytes());
+
+        let mut edited = metainfo.clone();
+        edited.announce = Some("http://other.test/announce".to_string());
+        let reparsed = Metainfo::from_bytes(&edited.to_bytes().unwrap()).unwrap();
+        assert_eq!(reparsed.info_hash, metainfo.info_hash);
+        assert_eq!(reparsed.announce, edited.announce);
+
+        // Without the original bytes, dropping `source` would change the hash
+        let mut rebuilt = metainfo.clone();
+        rebuilt.info_bytes = None;
+        assert!(matches!(
+            rebuilt.to_bytes(),
+            Err(crate::Error::InvalidMetainfo(_))
+        ));
+
+        let with_meta_version = "d8:announce9:localhost4:infod6:lengthi1000e\
+                                 12:meta versioni1e4:name4:test12:piece lengthi512e\
+                                 6:pieces40:1234567890123456789012345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(with_meta_version.as_bytes()).unwrap();
+        assert_eq!(metainfo.to_bytes().unwrap(), with_meta_version.as_bytes());
+    }
+
+    #[test]
+    fn test_v1_with_meta_version() {
+        let torrent = "d8:announce9:localhost4:infod6:lengthi1000e12:meta versioni1e\
+                       4:name4:test12:piece lengthi512e6:pieces40:\
+                       1234567890123456789012345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        assert_eq!(metainfo.meta_version, Some(1));
+        assert_eq!(metainfo.num_pieces(), 2);
+        assert!(matches!(
+            metainfo.info.files,
+            FileInfo::Single { length: 1000 }
+        ));
+
+        let metainfo = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        assert_eq!(metainfo.meta_version, None);
+    }
+
+    #[test]
+    fn test_non_utf8_comment() {
+        let mut torrent = b"d8:announce9:localhost7:comment4:ab".to_vec();
+        torrent.extend_from_slice(&[0xFF, 0xFE]);
+        torrent.extend_from_slice(b"10:created by3:");
+        torrent.extend_from_slice(&[0xC3, 0x28, b'x']);
+        torrent.extend_from_slice(
+            b"4:infod6:lengthi1000e4:name8:test.txt12:piece lengthi512e6:pieces20:12345678901234567890ee",
+        );
+
+        let metainfo = Metainfo::from_bytes(&torrent).unwrap();
+
+        assert_eq!(metainfo.comment_bytes.as_deref(), Some(&b"ab\xFF\xFE"[..]));
+        assert_eq!(metainfo.comment.as_deref(), Some("ab\u{FFFD}\u{FFFD}"));
+        assert_eq!(metainfo.created_by_bytes.as_deref(), Some(&b"\xC3(x"[..]));
+        assert_eq!(metainfo.created_by.as_deref(), Some("\u{FFFD}(x"));
+    }
+
+    #[test]
+    fn test_duplicate_file_paths_rejected() {
+        for second_path in ["l3:dir4:filee", "l3:dir0:1:.4:filee"] {
+            let torrent = format!(
+                "d8:announce9:localhost4:infod5:filesl\
+                 d6:lengthi10e4:pathl3:dir4:fileee\
+                 d6:lengthi20e4:path{}e\
+                 e4:name4:root12:piece lengthi512e\
+                 6:pieces20:12345678901234567890ee",
+                second_path
+            );
+
+            let result = Metainfo::from_bytes(torrent.as_bytes());
+            assert!(
+                matches!(result, Err(crate::Error::InvalidMetainfo(msg)) if msg == "duplicate file path")
+            );
+        }
+
+        // Components that could name the same file another way, or escape
+        // the download directory, are rejected outright
+        for second_path in [
+            "l3:dir2:..3:dir4:filee",
+            "l2:..4:filee",
+            "l5:/file4:filee",
+            "l8:dir/file4:filee",
+            "l8:dir\\file4:filee",
+        ] {
+            let torrent = format!(
+                "d8:announce9:localhost4:infod5:filesl\
+                 d6:lengthi10e4:pathl3:dir4:fileee\
+                 d6:lengthi20e4:path{}e\
+                 e4:name4:root12:piece lengthi512e\
+                 6:pieces20:12345678901234567890ee",
+                second_path
+            );
+
+            let result = Metainfo::from_bytes(torrent.as_bytes());
+            assert!(
+                matches!(result, Err(crate::Error::InvalidMetainfo(ref msg)) if msg.starts_with("unsafe")),
+                "{}: {:?}",
+                second_path,
+                result
+            );
+        }
+
+        let escaping_name = "d8:announce9:localhost4:infod6:lengthi10e4:name2:..\
+                             12:piece lengthi512e6:pieces20:12345678901234567890ee";
+        assert!(Metainfo::from_bytes(escaping_name.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_pieces_as_list() {
+        let torrent = "d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                       12:piece lengthi512e6:piecesl\
+                       20:aaaaaaaaaaaaaaaaaaaa20:bbbbbbbbbbbbbbbbbbbbeee";
+
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.info.pieces, vec![[b'a'; 20], [b'b'; 20]]);
+
+        let bad = "d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                   12:piece lengthi512e6:piecesl20:aaaaaaaaaaaaaaaaaaaa3:bbbeee";
+        assert!(Metainfo::from_bytes(bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_metadata_only_skips_pieces() {
+        let torrent = "d8:announce9:localhost7:comment2:hi4:infod6:lengthi1000e\
+                       4:name8:test.txt12:piece lengthi512e\
+                       6:pieces40:1234567890123456789012345678901234567890ee";
+
+        let full = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        let metadata = Metainfo::from_bytes_metadata_only(torrent.as_bytes()).unwrap();
+
+        assert!(metadata.info.pieces.is_empty());
+        assert_eq!(metadata.num_pieces(), 2);
+        assert_eq!(metadata.info_hash, full.info_hash);
+        assert_eq!(metadata.info.name, "test.txt");
+        assert_eq!(metadata.info.piece_length, 512);
+        assert_eq!(metadata.total_size(), 1000);
+        assert_eq!(metadata.comment.as_deref(), Some("hi"));
+        assert_eq!(metadata.announce.as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn test_missing_required_field() {
+        let torrent = b"d8:announce9:localhost4:infod4:name4:test12:piece lengthi512e6:pieces0:ee";
+        assert!(matches!(
+            Metainfo::from_bytes(torrent),
+            Err(crate::Error::InvalidMetainfo(_))
+        ));
+    }
+
+    #[test]
+    fn test_probe_trackers() {
+        use crate::tracker::mock::MockTracker;
+        use std::net::TcpListener;
+
+        let live = MockTracker::start();
+        let live_url = live.announce_url();
+
+        let dead_url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/announce", listener.local_addr().unwrap())
+        };
+
+        let torrent = format!(
+            "d8:announce{}:{}13:announce-listll{}:{}ee\
+             4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+             6:pieces20:12345678901234567890ee",
+            live_url.len(),
+            live_url,
+            dead_url.len(),
+            dead_url
+        );
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        let mut results = metainfo.probe_trackers(Duration::from_secs(5));
+        results.sort();
+        let mut expected = vec![(live_url, true), (dead_url, false)];
+        expected.sort();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_probe_more_trackers_than_workers() {
+        use crate::tracker::mock::MockTracker;
+
+        let live = MockTracker::start();
+        let urls: Vec<String> = (0..MAX_TRACKER_PROBES * 2 + 1)
+            .map(|i| format!("{}?n={}", live.announce_url(), i))
+            .collect();
+        let tier: String = urls
+            .iter()
+            .map(|url| format!("{}:{}", url.len(), url))
+            .collect();
+        let torrent = format!(
+            "d13:announce-listll{}ee\
+             4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+             6:pieces20:12345678901234567890ee",
+            tier
+        );
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        let results = metainfo.probe_trackers(Duration::from_secs(5));
+        assert_eq!(
+            results,
+            metainfo
+                .all_trackers()
+                .into_iter()
+                .map(|url| (url, true))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(live.requests().len(), urls.len());
+    }
+
+    #[test]
+    fn test_dht_bootstrap_nodes() {
+        let torrent = "d8:announce9:localhost\
+                       4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:12345678901234567890e\
+                       5:nodesl\
+                       l11:router.testi6881ee\
+                       l11:router.testi6881ee\
+                       l9:127.0.0.1i6882ee\
+                       l0:i6881ee\
+                       l11:router.testi0ee\
+                       l11:router.testi70000ee\
+                       i42e\
+                       l11:router.teste\
+                       ee";
+
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        assert_eq!(metainfo.nodes.len(), 5);
+        assert_eq!(
+            metainfo.dht_bootstrap_nodes(),
+            vec![
+                ("router.test".to_string(), 6881),
+                ("127.0.0.1".to_string(), 6882)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_private_torrent_has_no_dht_nodes() {
+        let torrent = "d8:announce9:localhost\
+                       4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:123456789012345678907:privatei1ee\
+                       5:nodesll11:router.testi6881eee\
+                       e";
+
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.dht_nodes().len(), 1);
+        assert!(metainfo.dht_bootstrap_nodes().is_empty());
+    }
+
+    #[test]
+    fn test_trackerless_torrent() {
+        let torrent = "d4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:12345678901234567890e\
+                       5:nodesll11:router.testi6881eel9:127.0.0.1i6882eee\
+                       e";
+
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.announce, None);
+        assert!(metainfo.all_trackers().is_empty());
+        assert!(metainfo.announce_set(1).is_empty());
+        assert_eq!(
+            metainfo.dht_nodes(),
+            vec![
+                ("router.test".to_string(), 6881),
+                ("127.0.0.1".to_string(), 6882)
+            ]
+        );
+        assert_eq!(metainfo.to_bytes().unwrap(), torrent.as_bytes());
+    }
+
+    #[test]
+    fn test_nodes6() {
+        let torrent = "d8:announce9:localhost\
+                       4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:12345678901234567890e\
+                       5:nodesll11:router.testi6881eee\
+                       6:nodes6ll11:2001:db8::1i6881eel11:router.testi6881eee\
+                       e";
+
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(
+            metainfo.nodes6,
+            vec![
+                ("2001:db8::1".to_string(), 6881),
+                ("router.test".to_string(), 6881)
+            ]
+        );
+        assert_eq!(
+            metainfo.dht_bootstrap_nodes(),
+            vec![
+                ("router.test".to_string(), 6881),
+                ("2001:db8::1".to_string(), 6881)
+            ]
+        );
+        assert_eq!(metainfo.to_bytes().unwrap(), torrent.as_bytes());
+    }
+
+    #[test]
+    fn test_creation_tool() {
+        let mut metainfo = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        let tool = |metainfo: &mut Metainfo, created_by: &str| {
+            metainfo.created_by = Some(created_by.to_string());
+            metainfo.creation_tool()
+        };
+        let some = |name: &str, version: Option<&str>| {
+            Some((name.to_string(), version.map(str::to_string)))
+        };
+
+        assert_eq!(
+            tool(&mut metainfo, "mktorrent 1.1"),
+            some("mktorrent", Some("1.1"))
+        );
+        assert_eq!(
+            tool(&mut metainfo, "qBittorrent v4.3.1"),
+            some("qBittorrent", Some("4.3.1"))
+        );
+        assert_eq!(
+            tool(&mut metainfo, "Transmission/2.94 (d00c8d9)"),
+            some("Transmission", Some("2.94"))
+        );
+        assert_eq!(tool(&mut metainfo, "libtorrent"), some("libtorrent", None));
+
+        assert_eq!(tool(&mut metainfo, "made by hand, no version"), None);
+        assert_eq!(tool(&mut metainfo, "  "), None);
+        metainfo.created_by = None;
+        assert_eq!(metainfo.creation_tool(), None);
+    }
+
+    #[test]
+    fn test_url_list() {
+        let torrent = |url_list: &str| {
+            format!(
+                "d8:announce9:localhost\
+                 4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                 6:pieces20:12345678901234567890e\
+                 8:url-list{}e",
+                url_list
+            )
+        };
+        let parse = |url_list: &str| Metainfo::from_bytes(torrent(url_list).as_bytes()).unwrap();
+
+        let single = parse("18:http://seed.test/a");
+        assert_eq!(single.url_list, vec!["http://seed.test/a"]);
+        assert_eq!(single.web_seeds(), vec!["http://seed.test/a"]);
+
+        let list = parse("l18:http://seed.test/a0:18:http://seed.test/a18:http://seed.test/be");
+        assert_eq!(list.url_list.len(), 4);
+        assert_eq!(
+            list.web_seeds(),
+            vec!["http://seed.test/a", "http://seed.test/b"]
+        );
+        assert_eq!(
+            list.to_bytes().unwrap(),
+            torrent("l18:http://seed.test/a0:18:http://seed.test/a18:http://seed.test/be")
+                .as_bytes()
+        );
+
+        // One bad entry drops only itself, not the torrent
+        let mixed = parse("l18:http://seed.test/ai1eld1:xi2eee18:http://seed.test/be");
+        assert_eq!(
+            mixed.web_seeds(),
+            vec!["http://seed.test/a", "http://seed.test/b"]
+        );
+        assert!(parse("i1e").url_list.is_empty());
+    }
+
+    #[test]
+    fn test_announce_list_with_non_utf8_url() {
+        let mut torrent = b"d8:announce9:tracker-113:announce-listll9:tracker-1\
+                            9:tracker-2el8:tracker"
+            .to_vec();
+        torrent.push(0xFF);
+        torrent.extend_from_slice(
+            b"ee4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+              6:pieces20:12345678901234567890ee",
+        );
+
+        let metainfo = Metainfo::from_bytes(&torrent).unwrap();
+
+        assert_eq!(
+            metainfo.announce_list,
+            vec![
+                vec!["tracker-1".to_string(), "tracker-2".to_string()],
+                vec!["tracker\u{FFFD}".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_announce_set_one_per_tier() {
+        let torrent = "d8:announce3:t-113:announce-listl\
+                       l3:t-13:t-2el3:t-33:t-1el3:t-4ee\
+                       4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:12345678901234567890ee";
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+
+        assert_eq!(metainfo.announce_set(1), vec!["t-1", "t-3", "t-4"]);
+        assert_eq!(metainfo.announce_set(2), vec!["t-1", "t-2", "t-3", "t-4"]);
+        assert!(metainfo.announce_set(0).is_empty());
+
+        let single = Metainfo::from_bytes(&create_test_torrent()).unwrap();
+        assert_eq!(single.announce_set(1), vec!["localhost"]);
+    }
+
+    #[test]
+    fn test_shuffle_trackers() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let torrent = "d8:announce3:t-113:announce-listl\
+                       l3:t-13:t-23:t-33:t-43:t-5el3:t-6el3:t-73:t-8ee\
+                       4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:12345678901234567890ee";
+        let mut metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        let parsed = metainfo.announce_list.clone();
+
+        metainfo.shuffle_trackers(&mut StdRng::seed_from_u64(7));
+
+        // Same seed, same order; each tier keeps its own trackers
+        let mut again = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        again.shuffle_trackers(&mut StdRng::seed_from_u64(7));
+        assert_eq!(again.announce_list, metainfo.announce_list);
+        assert_eq!(metainfo.announce_list.len(), parsed.len());
+        for (shuffled, original) in metainfo.announce_list.iter().zip(&parsed) {
+            let mut sorted = shuffled.clone();
+            sorted.sort();
+            assert_eq!(&sorted, original);
+        }
+
+        // Some seed must actually reorder the five-tracker tier
+        assert!((0..16).any(|seed| {
+            let mut other = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+            other.shuffle_trackers(&mut StdRng::seed_from_u64(seed));
+            other.announce_list[0] != parsed[0]
+        }));
+    }
+
+    #[test]
+    fn test_all_trackers_deduplication() {
+        let torrent = "d8:announce9:tracker-113:announce-listll9:tracker-1e\
+                       l9:tracker-2el9:tracker-1ee\
+                       4:infod6:lengthi1000e4:name4:test12:piece lengthi512e\
+                       6:pieces20:12345678901234567890ee";
+
+        let metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        let trackers = metainfo.all_trackers();
+
+        // Should deduplicate tracker-1
+        assert_eq!(trackers.len(), 2);
+    }
+}
@@ -0,0 +1,420 @@
+//! Access to a torrent's content on disk
+//!
+//! Files are expected under a base directory using the paths from
+//! [`Info::files`](super::Info::files).
+
+use super::Metainfo;
+use crate::Bitfield;
+use sha1::{Digest, Sha1};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Take};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+impl Metainfo {
+    /// Location of each file under `base`, in torrent order
+    pub fn file_paths(&self, base: &Path) -> Vec<PathBuf> {
+        self.info
+            .files()
+            .iter()
+            .map(|file| file.path.iter().fold(base.to_path_buf(), |p, c| p.join(c)))
+            .collect()
+    }
+
+    /// Read the whole download as one stream, files concatenated in order
+    ///
+    /// Each file contributes exactly its declared length. Padding files are
+    /// skipped, since they aren't stored. Fails if any other file is missing;
+    /// reading fails with `UnexpectedEof` once it reaches the end of a file
+    /// shorter than its declared length.
+    pub fn read_content(&self, base: &Path) -> crate::Result<impl Read> {
+        let files = self
+            .info
+            .files()
+            .iter()
+            .zip(self.file_paths(base))
+            .filter(|(entry, _)| !entry.is_padding())
+            .map(|(entry, path)| Ok((File::open(&path)?.take(entry.length), path)))
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        Ok(ContentReader { files, current: 0 })
+    }
+
+    /// SHA-1 of the whole content under `base`, files concatenated in order
+    ///
+    /// For checking against a hash published for the full download,
+    /// independent of the piece hashes. Fails if a file is missing or
+    /// shorter than its declared length rather than hashing partial content.
+    pub fn content_hash(&self, base: &Path) -> crate::Result<[u8; 20]> {
+        let mut hasher = Sha1::new();
+        io::copy(&mut self.read_content(base)?, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Hash every piece on disk under `base`, reporting progress as it goes
+    ///
+    /// `progress` is called with `(pieces_checked, total_pieces)` after each
+    /// piece. Pieces whose files are missing or too short count as absent;
+    /// other I/O errors abort the pass. Padding files are not read from disk
+    /// but hashed as the zeros they stand for.
+    ///
+    /// Setting `cancel` from another thread stops the pass after the piece
+    /// being hashed; the bitfield then holds only the pieces checked so far.
+    pub fn verify_with_progress(
+        &self,
+        base: &Path,
+        mut progress: impl FnMut(usize, usize),
+        cancel: &AtomicBool,
+    ) -> crate::Result<Bitfield> {
+        let total = self.info.num_pieces();
+        let mut bitfield = Bitfield::new(total);
+        self.check_pieces(base, |index, valid| {
+            if valid {
+                bitfield.set(index);
+            }
+            progress(index + 1, total);
+            if cancel.load(Ordering::Relaxed) {
+                tracing::debug!(
+                    "Verification cancelled after {} of {} pieces",
+                    index + 1,
+                    total
+                );
+                return false;
+            }
+            true
+        })?;
+        Ok(bitfield)
+    }
+
+    /// Global byte offset of the first piece under `base` that fails its hash
+    ///
+    /// Pieces are checked in order and checking stops at the first failure,
+    /// so the offset is the start of that piece. Missing data counts as a
+    /// failure. Returns `None` if every piece passes.
+    pub fn find_first_corruption(&self, base: &Path) -> crate::Result<Option<u64>> {
+        let mut first_bad = None;
+        self.check_pieces(base, |index, valid| {
+            if !valid {
+                first_bad = Some(index as u64 * self.info.piece_length);
+            }
+            valid
+        })?;
+        Ok(first_bad)
+    }
+
+    /// Hash pieces in order, telling `on_piece` whether each one matches
+    ///
+    /// Stops early once `on_piece` returns false.
+    fn check_pieces(
+        &self,
+        base: &Path,
+        mut on_piece: impl FnMut(usize, bool) -> bool,
+    ) -> crate::Result<()> {
+        let mut files = self
+            .info
+            .files()
+            .iter()
+            .zip(self.file_paths(base))
+            .map(|(entry, path)| {
+                if entry.is_padding() {
+                    return Ok(Source::Padding);
+                }
+                match File::open(path) {
+                    Ok(file) => Ok(Source::File(file)),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Source::Missing),
+                    Err(e) => Err(e),
+                }
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut buf = Vec::new();
+        for index in 0..self.info.num_pieces() {
+            let plan = self.info.piece_write_plan(index);
+            buf.resize(plan.last().map_or(0, |(_, _, range)| range.end), 0);
+
+            let valid = read_piece(&mut files, &plan, &mut buf)?
+                && self.info.pieces.get(index) == Some(&Sha1::digest(&buf).into());
+            if !on_piece(index, valid) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the bytes of one file come from when checking pieces
+enum Source {
+    File(File),
+    Missing,
+    /// A BEP 47 padding file, all zeros
+    Padding,
+}
+
+/// Fill `buf` with a piece following its write plan
+///
+/// Returns false if any part of it is missing from disk.
+fn read_piece(
+    files: &mut [Source],
+    plan: &[(usize, u64, std::ops::Range<usize>)],
+    buf: &mut [u8],
+) -> io::Result<bool> {
+    for (file_index, offset, range) in plan {
+        let file = match &mut files[*file_index] {
+            Source::File(file) => file,
+            Source::Missing => return Ok(false),
+            Source::Padding => {
+                buf[range.clone()].fill(0);
+                continue;
+            }
+        };
+        file.seek(SeekFrom::Start(*offset))?;
+        match file.read_exact(&mut buf[range.clone()]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads a sequence of files back to back
+struct ContentReader {
+    files: Vec<(Take<File>, PathBuf)>,
+    current: usize,
+}
+
+impl Read for ContentReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some((file, path)) = self.files.get_mut(self.current) {
+            let n = file.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            // Running out before the declared length would shift every
+            // later byte of the content
+            if file.limit() > 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} is {} bytes short", path.display(), file.limit()),
+                ));
+            }
+            self.current += 1;
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metainfo::{PieceLength, create_from_path};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn two_file_torrent() -> Metainfo {
+        let torrent = "d8:announce9:localhost4:infod5:filesl\
+                       d6:lengthi5e4:pathl1:aee\
+                       d6:lengthi7e4:pathl3:sub1:bee\
+                       e4:name4:root12:piece lengthi16384e\
+                       6:pieces20:12345678901234567890ee";
+        Metainfo::from_bytes(torrent.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_read_content_concatenates_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("root/sub")).unwrap();
+        fs::write(dir.path().join("root/a"), b"hello").unwrap();
+        // Bytes past the declared length are not part of the content
+        fs::write(dir.path().join("root/sub/b"), b"torrent!!").unwrap();
+
+        let mut content = Vec::new();
+        two_file_torrent()
+            .read_content(dir.path())
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+
+        assert_eq!(content, b"hellotorrent");
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("root/sub")).unwrap();
+        fs::write(dir.path().join("root/a"), b"hello").unwrap();
+        fs::write(dir.path().join("root/sub/b"), b"torrent").unwrap();
+
+        // sha1sum of "hellotorrent"
+        assert_eq!(
+            hex::encode(two_file_torrent().content_hash(dir.path()).unwrap()),
+            "9b01b73a3d8554abf62d137dfeb0272fcbf1ed86"
+        );
+
+        fs::write(dir.path().join("root/sub/b"), b"torr").unwrap();
+        assert!(matches!(
+            two_file_torrent().content_hash(dir.path()),
+            Err(crate::Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_verify_with_progress() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a"), vec![1u8; 20]).unwrap();
+        fs::write(root.join("b"), vec![2u8; 20]).unwrap();
+
+        let mut metainfo = create_from_path(&root, "http://t", PieceLength::Auto).unwrap();
+        // Use tiny pieces so the content spans several of them
+        metainfo.info.piece_length = 16;
+        let content: Vec<u8> = [vec![1u8; 20], vec![2u8; 20]].concat();
+        metainfo.info.pieces = content
+            .chunks(16)
+            .map(|chunk| Sha1::digest(chunk).into())
+            .collect();
+
+        // Corrupt a byte in the middle piece
+        fs::write(root.join("b"), [vec![9u8], vec![2u8; 19]].concat()).unwrap();
+
+        let mut calls = Vec::new();
+        let bitfield = metainfo
+            .verify_with_progress(
+                dir.path(),
+                |done, total| calls.push((done, total)),
+                &AtomicBool::new(false),
+            )
+            .unwrap();
+
+        assert_eq!(calls, vec![(1, 3), (2, 3), (3, 3)]);
+        assert!(bitfield.has(0));
+        assert!(!bitfield.has(1));
+        assert!(bitfield.has(2));
+
+        // A missing file only marks its pieces as absent
+        fs::remove_file(root.join("b")).unwrap();
+        let bitfield = metainfo
+            .verify_with_progress(dir.path(), |_, _| {}, &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(bitfield.count(), 1);
+    }
+
+    #[test]
+    fn test_verify_cancelled() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, vec![4u8; 100_000]).unwrap();
+        let metainfo = create_from_path(&path, "http://t", PieceLength::Fixed(16 * 1024)).unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let mut checked = 0;
+        let bitfield = metainfo
+            .verify_with_progress(
+                dir.path(),
+                |done, _| {
+                    checked = done;
+                    cancel.store(true, Ordering::Relaxed);
+                },
+                &cancel,
+            )
+            .unwrap();
+
+        assert_eq!(checked, 1);
+        assert_eq!(bitfield.len(), metainfo.num_pieces());
+        assert!(bitfield.has(0));
+        assert_eq!(bitfield.count(), 1);
+    }
+
+    #[test]
+    fn test_find_first_corruption() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, vec![4u8; 100_000]).unwrap();
+        let metainfo = create_from_path(&path, "http://t", PieceLength::Fixed(16 * 1024)).unwrap();
+
+        assert_eq!(metainfo.find_first_corruption(dir.path()).unwrap(), None);
+
+        // Damage bytes in pieces 2 and 4; only the first one is reported
+        let mut data = vec![4u8; 100_000];
+        data[40_000] = 0;
+        data[70_000] = 0;
+        fs::write(&path, data).unwrap();
+
+        assert_eq!(
+            metainfo.find_first_corruption(dir.path()).unwrap(),
+            Some(2 * 16 * 1024)
+        );
+    }
+
+    #[test]
+    fn test_padding_files() {
+        let torrent = "d8:announce9:localhost4:infod5:filesl\
+                       d6:lengthi5e4:pathl1:aee\
+                       d4:attr1:p6:lengthi11e4:pathl4:.pad2:11ee\
+                       d6:lengthi7e4:pathl1:bee\
+                       e4:name4:root12:piece lengthi16e\
+                       6:pieces40:1234567890123456789012345678901234567890ee";
+        let mut metainfo = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        assert_eq!(metainfo.total_size(), 23);
+        assert_eq!(metainfo.content_size(), 12);
+
+        let first_piece = [b"hello".as_slice(), &[0u8; 11]].concat();
+        metainfo.info.pieces = vec![
+            Sha1::digest(&first_piece).into(),
+            Sha1::digest(b"torrent").into(),
+        ];
+
+        // Only the real files exist on disk
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("root")).unwrap();
+        fs::write(dir.path().join("root/a"), b"hello").unwrap();
+        fs::write(dir.path().join("root/b"), b"torrent").unwrap();
+
+        let bitfield = metainfo
+            .verify_with_progress(dir.path(), |_, _| {}, &AtomicBool::new(false))
+            .unwrap();
+        assert!(bitfield.is_complete());
+
+        let mut content = Vec::new();
+        metainfo
+            .read_content(dir.path())
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"hellotorrent");
+
+        assert_eq!(
+            metainfo.info.completed_files(&bitfield),
+            vec![PathBuf::from("root/a"), PathBuf::from("root/b")]
+        );
+    }
+
+    #[test]
+    fn test_read_content_short_file() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("root/sub")).unwrap();
+        fs::write(dir.path().join("root/a"), b"hel").unwrap();
+        fs::write(dir.path().join("root/sub/b"), b"torrent").unwrap();
+
+        let error = two_file_torrent()
+            .read_content(dir.path())
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_read_content_missing_file() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("root")).unwrap();
+        fs::write(dir.path().join("root/a"), b"hello").unwrap();
+
+        assert!(matches!(
+            two_file_torrent().read_content(dir.path()),
+            Err(crate::Error::Io(_))
+        ));
+    }
+}
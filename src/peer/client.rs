@@ -0,0 +1,182 @@
+//! Client identification from peer IDs
+//!
+//! Most clients start their peer ID with a recognisable prefix. Azureus
+//! style is `-XXvvvv-`: a two-letter client code and four version
+//! characters. The older Shadow style is one client letter followed by up
+//! to five version characters and a run of dashes.
+
+/// Two-letter Azureus-style client codes
+const AZUREUS_CLIENTS: &[(&[u8; 2], &str)] = &[
+    (b"AZ", "Vuze"),
+    (b"BC", "BitComet"),
+    (b"BI", "BiglyBT"),
+    (b"BT", "BitTorrent"),
+    (b"DE", "Deluge"),
+    (b"FD", "Free Download Manager"),
+    (b"KT", "KTorrent"),
+    (b"LT", "libtorrent"),
+    (b"lt", "libTorrent (rTorrent)"),
+    (b"qB", "qBittorrent"),
+    (b"TR", "Transmission"),
+    (b"TX", "Tixati"),
+    (b"UM", "uTorrent Mac"),
+    (b"UT", "uTorrent"),
+    (b"UW", "uTorrent Web"),
+    (b"WW", "WebTorrent"),
+];
+
+/// Shadow-style client letters
+const SHADOW_CLIENTS: &[(u8, &str)] = &[
+    (b'A', "ABC"),
+    (b'O', "Osprey Permaseed"),
+    (b'Q', "BTQueue"),
+    (b'R', "Tribler"),
+    (b'S', "Shadow"),
+    (b'T', "BitTornado"),
+    (b'U', "UPnP NAT Bit Torrent"),
+];
+
+/// Shadow-style version digits, each worth its index
+const SHADOW_DIGITS: &[u8; 64] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz.-";
+
+/// Software a peer claims to run, as read from its peer ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// Client name, such as `Transmission`
+    pub name: String,
+    /// Dotted version, such as `2.94`
+    pub version: String,
+}
+
+/// Identify the client that generated `peer_id`
+///
+/// Returns `None` when the ID follows neither convention or names a client
+/// not in the built-in tables.
+pub fn identify_client(peer_id: &[u8; 20]) -> Option<ClientInfo> {
+    azureus_style(peer_id).or_else(|| shadow_style(peer_id))
+}
+
+fn azureus_style(peer_id: &[u8; 20]) -> Option<ClientInfo> {
+    if peer_id[0] != b'-' || peer_id[7] != b'-' {
+        return None;
+    }
+    let code = &peer_id[1..3];
+    let (_, name) = AZUREUS_CLIENTS.iter().find(|(c, _)| &c[..] == code)?;
+    let digits = &peer_id[3..7];
+    if !digits.iter().all(u8::is_ascii_alphanumeric) {
+        return None;
+    }
+
+    let version = if code == b"TR" {
+        // Transmission packs a major digit and a two-digit minor
+        format!(
+            "{}.{}",
+            digits[0] as char,
+            std::str::from_utf8(&digits[1..3]).ok()?
+        )
+    } else {
+        // Letters continue the digits (`F` is 15); the last character is
+        // often a build tag rather than a version number
+        let mut parts: Vec<String> = digits[..3]
+            .iter()
+            .map(|&d| azureus_digit(d).to_string())
+            .collect();
+        if digits[3].is_ascii_digit() && digits[3] != b'0' {
+            parts.push((digits[3] as char).to_string());
+        }
+        parts.join(".")
+    };
+
+    Some(ClientInfo {
+        name: name.to_string(),
+        version,
+    })
+}
+
+fn azureus_digit(digit: u8) -> u32 {
+    (digit as char).to_digit(36).unwrap_or(0)
+}
+
+fn shadow_style(peer_id: &[u8; 20]) -> Option<ClientInfo> {
+    let (_, name) = SHADOW_CLIENTS.iter().find(|(c, _)| *c == peer_id[0])?;
+
+    // Version characters run until a `--`, at most five of them
+    let end = (2..=6).find(|&i| &peer_id[i..i + 2] == b"--")?;
+    let version = peer_id[1..end]
+        .iter()
+        .map(|d| {
+            SHADOW_DIGITS
+                .iter()
+                .position(|s| s == d)
+                .map(|n| n.to_string())
+        })
+        .collect::<Option<Vec<_>>>()?
+        .join(".");
+
+    Some(ClientInfo {
+        name: name.to_string(),
+        version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(prefix: &[u8]) -> [u8; 20] {
+        let mut peer_id = [b'x'; 20];
+        peer_id[..prefix.len()].copy_from_slice(prefix);
+        peer_id
+    }
+
+    fn client(name: &str, version: &str) -> Option<ClientInfo> {
+        Some(ClientInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_azureus_style() {
+        assert_eq!(
+            identify_client(b"-TR2940-k8hj0wgej6ch"),
+            client("Transmission", "2.94")
+        );
+        assert_eq!(
+            identify_client(&id(b"-qB4250-")),
+            client("qBittorrent", "4.2.5")
+        );
+        assert_eq!(
+            identify_client(&id(b"-UT355S-")),
+            client("uTorrent", "3.5.5")
+        );
+        assert_eq!(
+            identify_client(&id(b"-DE13F0-")),
+            client("Deluge", "1.3.15")
+        );
+        assert_eq!(
+            identify_client(&id(b"-LT1230-")),
+            client("libtorrent", "1.2.3")
+        );
+    }
+
+    #[test]
+    fn test_shadow_style() {
+        assert_eq!(
+            identify_client(&id(b"S58B-----")),
+            client("Shadow", "5.8.11")
+        );
+        assert_eq!(
+            identify_client(&id(b"T03I--")),
+            client("BitTornado", "0.3.18")
+        );
+    }
+
+    #[test]
+    fn test_unknown_clients() {
+        assert_eq!(identify_client(&id(b"-ZZ1000-")), None);
+        assert_eq!(identify_client(&[0u8; 20]), None);
+        assert_eq!(identify_client(&id(b"-TR29")), None);
+    }
+}
@@ -0,0 +1,119 @@
+//! The BitTorrent handshake
+//!
+//! The first message on every peer connection, in both directions:
+//! `<pstrlen=19><"BitTorrent protocol"><8 reserved bytes><info_hash><peer_id>`.
+
+/// Protocol string every handshake starts with
+const PROTOCOL: &[u8; 19] = b"BitTorrent protocol";
+
+/// Length of a handshake on the wire
+pub const HANDSHAKE_LEN: usize = 68;
+
+/// A peer handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handshake {
+    /// Extension bits, all zero unless extensions are negotiated
+    pub reserved: [u8; 8],
+    /// Torrent the connection is for
+    pub info_hash: [u8; 20],
+    /// ID of the sending peer
+    pub peer_id: [u8; 20],
+}
+
+impl Handshake {
+    /// A handshake with no extension bits set
+    pub fn new(info_hash: [u8; 20], peer_id: [u8; 20]) -> Self {
+        Self {
+            reserved: [0; 8],
+            info_hash,
+            peer_id,
+        }
+    }
+
+    /// Encode for the wire
+    pub fn to_bytes(&self) -> [u8; HANDSHAKE_LEN] {
+        let mut bytes = [0u8; HANDSHAKE_LEN];
+        bytes[0] = PROTOCOL.len() as u8;
+        bytes[1..20].copy_from_slice(PROTOCOL);
+        bytes[20..28].copy_from_slice(&self.reserved);
+        bytes[28..48].copy_from_slice(&self.info_hash);
+        bytes[48..68].copy_from_slice(&self.peer_id);
+        bytes
+    }
+
+    /// Decode a handshake, which must be exactly [`HANDSHAKE_LEN`] bytes
+    pub fn from_bytes(bytes: &[u8]) -> crate::Result<Self> {
+        if bytes.len() != HANDSHAKE_LEN {
+            return Err(crate::Error::Handshake(format!(
+                "expected {} bytes, got {}",
+                HANDSHAKE_LEN,
+                bytes.len()
+            )));
+        }
+        if bytes[0] as usize != PROTOCOL.len() || &bytes[1..20] != PROTOCOL {
+            return Err(crate::Error::Handshake(
+                "not a BitTorrent protocol handshake".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            reserved: bytes[20..28].try_into().unwrap(),
+            info_hash: bytes[28..48].try_into().unwrap(),
+            peer_id: bytes[48..68].try_into().unwrap(),
+        })
+    }
+
+    /// Fail unless the handshake is for the torrent `info_hash`
+    pub fn check_info_hash(&self, info_hash: &[u8; 20]) -> crate::Result<()> {
+        if &self.info_hash != info_hash {
+            return Err(crate::Error::Handshake(format!(
+                "info hash mismatch: expected {}, got {}",
+                hex::encode(info_hash),
+                hex::encode(self.info_hash)
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_round_trip() {
+        let mut handshake = Handshake::new([7u8; 20], *b"-RS0100-abcdefghijkl");
+        handshake.reserved[5] = 0x10;
+
+        let bytes = handshake.to_bytes();
+        assert_eq!(bytes[0], 19);
+        assert_eq!(&bytes[1..20], b"BitTorrent protocol");
+        assert_eq!(Handshake::from_bytes(&bytes).unwrap(), handshake);
+    }
+
+    #[test]
+    fn test_malformed_handshake() {
+        let bytes = Handshake::new([7u8; 20], [1u8; 20]).to_bytes();
+        assert!(matches!(
+            Handshake::from_bytes(&bytes[..67]),
+            Err(crate::Error::Handshake(_))
+        ));
+
+        let mut wrong_protocol = bytes;
+        wrong_protocol[1] = b'b';
+        assert!(matches!(
+            Handshake::from_bytes(&wrong_protocol),
+            Err(crate::Error::Handshake(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_info_hash() {
+        let handshake = Handshake::new([7u8; 20], [1u8; 20]);
+        assert!(handshake.check_info_hash(&[7u8; 20]).is_ok());
+        assert!(matches!(
+            handshake.check_info_hash(&[8u8; 20]),
+            Err(crate::Error::Handshake(_))
+        ));
+    }
+}
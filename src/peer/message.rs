@@ -0,0 +1,247 @@
+//! Peer wire protocol messages
+//!
+//! After the handshake every message is framed as a 4-byte big-endian length
+//! followed by that many bytes: a 1-byte message id and its payload. A zero
+//! length is a keep-alive with no id.
+
+const CHOKE: u8 = 0;
+const UNCHOKE: u8 = 1;
+const INTERESTED: u8 = 2;
+const NOT_INTERESTED: u8 = 3;
+const HAVE: u8 = 4;
+const BITFIELD: u8 = 5;
+const REQUEST: u8 = 6;
+const PIECE: u8 = 7;
+const CANCEL: u8 = 8;
+
+/// A message exchanged with a peer after the handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    KeepAlive,
+    Choke,
+    Unchoke,
+    Interested,
+    NotInterested,
+    /// The sender finished piece `index`
+    Have(u32),
+    /// Packed piece availability, sent right after the handshake
+    Bitfield(Vec<u8>),
+    /// Ask for `length` bytes of piece `index` starting at `begin`
+    Request {
+        index: u32,
+        begin: u32,
+        length: u32,
+    },
+    /// A block of piece `index` starting at `begin`
+    Piece {
+        index: u32,
+        begin: u32,
+        block: Vec<u8>,
+    },
+    /// Withdraw an earlier request
+    Cancel {
+        index: u32,
+        begin: u32,
+        length: u32,
+    },
+}
+
+impl Message {
+    /// Encode as a length-prefixed frame
+    pub fn encode(&self) -> Vec<u8> {
+        let (id, payload): (u8, Vec<u8>) = match self {
+            Message::KeepAlive => return vec![0; 4],
+            Message::Choke => (CHOKE, Vec::new()),
+            Message::Unchoke => (UNCHOKE, Vec::new()),
+            Message::Interested => (INTERESTED, Vec::new()),
+            Message::NotInterested => (NOT_INTERESTED, Vec::new()),
+            Message::Have(index) => (HAVE, index.to_be_bytes().to_vec()),
+            Message::Bitfield(bytes) => (BITFIELD, bytes.clone()),
+            Message::Request {
+                index,
+                begin,
+                length,
+            } => (
+                REQUEST,
+                [*index, *begin, *length].map(u32::to_be_bytes).concat(),
+            ),
+            Message::Piece {
+                index,
+                begin,
+                block,
+            } => (
+                PIECE,
+                [&index.to_be_bytes()[..], &begin.to_be_bytes(), block].concat(),
+            ),
+            Message::Cancel {
+                index,
+                begin,
+                length,
+            } => (
+                CANCEL,
+                [*index, *begin, *length].map(u32::to_be_bytes).concat(),
+            ),
+        };
+
+        let mut frame = Vec::with_capacity(5 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        frame.push(id);
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    /// Decode one complete length-prefixed frame
+    ///
+    /// Returns `Ok(None)` for a keep-alive. Fails if the frame is truncated,
+    /// has trailing bytes, carries an unknown id or has a payload of the
+    /// wrong size for its id.
+    pub fn decode(frame: &[u8]) -> crate::Result<Option<Message>> {
+        let Some((prefix, body)) = frame.split_first_chunk::<4>() else {
+            return Err(crate::Error::Peer(
+                "message shorter than its length prefix".to_string(),
+            ));
+        };
+        let length = u32::from_be_bytes(*prefix) as usize;
+        if body.len() != length {
+            return Err(crate::Error::Peer(format!(
+                "message length prefix says {} bytes, got {}",
+                length,
+                body.len()
+            )));
+        }
+        let Some((&id, payload)) = body.split_first() else {
+            return Ok(None);
+        };
+
+        let message = match id {
+            CHOKE | UNCHOKE | INTERESTED | NOT_INTERESTED => {
+                expect_len(id, payload, 0)?;
+                match id {
+                    CHOKE => Message::Choke,
+                    UNCHOKE => Message::Unchoke,
+                    INTERESTED => Message::Interested,
+                    _ => Message::NotInterested,
+                }
+            }
+            HAVE => {
+                expect_len(id, payload, 4)?;
+                Message::Have(read_u32(payload, 0))
+            }
+            BITFIELD => Message::Bitfield(payload.to_vec()),
+            REQUEST | CANCEL => {
+                expect_len(id, payload, 12)?;
+                let (index, begin, length) = (
+                    read_u32(payload, 0),
+                    read_u32(payload, 4),
+                    read_u32(payload, 8),
+                );
+                if id == REQUEST {
+                    Message::Request {
+                        index,
+                        begin,
+                        length,
+                    }
+                } else {
+                    Message::Cancel {
+                        index,
+                        begin,
+                        length,
+                    }
+                }
+            }
+            PIECE => {
+                if payload.len() < 8 {
+                    return Err(crate::Error::Peer("piece message too short".to_string()));
+                }
+                Message::Piece {
+                    index: read_u32(payload, 0),
+                    begin: read_u32(payload, 4),
+                    block: payload[8..].to_vec(),
+                }
+            }
+            _ => {
+                return Err(crate::Error::Peer(format!("unknown message id {}", id)));
+            }
+        };
+
+        Ok(Some(message))
+    }
+}
+
+fn expect_len(id: u8, payload: &[u8], len: usize) -> crate::Result<()> {
+    if payload.len() != len {
+        return Err(crate::Error::Peer(format!(
+            "message id {} expects a {}-byte payload, got {}",
+            id,
+            len,
+            payload.len()
+        )));
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_every_variant() {
+        let messages = [
+            Message::Choke,
+            Message::Unchoke,
+            Message::Interested,
+            Message::NotInterested,
+            Message::Have(42),
+            Message::Bitfield(vec![0b1010_0000, 0xFF]),
+            Message::Request {
+                index: 1,
+                begin: 16384,
+                length: 16384,
+            },
+            Message::Piece {
+                index: 3,
+                begin: 0,
+                block: b"block data".to_vec(),
+            },
+            Message::Cancel {
+                index: 1,
+                begin: 16384,
+                length: 16384,
+            },
+        ];
+
+        for message in messages {
+            let frame = message.encode();
+            assert_eq!(Message::decode(&frame).unwrap(), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        assert_eq!(Message::KeepAlive.encode(), vec![0, 0, 0, 0]);
+        assert_eq!(Message::decode(&[0, 0, 0, 0]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_wire_format() {
+        assert_eq!(Message::Have(1).encode(), vec![0, 0, 0, 5, 4, 0, 0, 0, 1]);
+        assert_eq!(Message::Unchoke.encode(), vec![0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        // Unknown id
+        assert!(Message::decode(&[0, 0, 0, 1, 20]).is_err());
+        // Truncated frame
+        assert!(Message::decode(&[0, 0, 0, 5, 4, 0, 0]).is_err());
+        assert!(Message::decode(&[0, 0]).is_err());
+        // Wrong payload size for `have`
+        assert!(Message::decode(&[0, 0, 0, 3, 4, 0, 0]).is_err());
+        // Payload on a message that takes none
+        assert!(Message::decode(&[0, 0, 0, 2, 0, 1]).is_err());
+    }
+}
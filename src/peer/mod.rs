@@ -1,6 +1,26 @@
 //! Peer-related utilities
 
+mod client;
+mod handshake;
+mod message;
+mod session;
+
+pub use client::{ClientInfo, identify_client};
+pub use handshake::{HANDSHAKE_LEN, Handshake};
+pub use message::Message;
+pub use session::{HANDSHAKE_TIMEOUT, PeerSession, handshake_all};
+
 use rand::Rng;
+use sha1::{Digest, Sha1};
+
+/// Fewest connections [`recommended_connections`] suggests
+pub const MIN_CONNECTIONS: usize = 4;
+
+/// Most connections [`recommended_connections`] suggests
+pub const MAX_CONNECTIONS: usize = 200;
+
+/// Bandwidth budgeted per connection, in bits per second
+const BPS_PER_CONNECTION: u64 = 64_000;
 
 /// Generate a random 20-byte peer ID
 ///
@@ -19,6 +39,36 @@ pub fn generate_peer_id() -> [u8; 20] {
     peer_id
 }
 
+/// Deterministic 20-byte peer ID: `prefix` followed by the leading bytes of
+/// the SHA-1 of `seed` (big-endian)
+///
+/// The same prefix and seed always give the same ID, across versions and
+/// platforms, for reproducible tests and clients that want a stable
+/// identity. A prefix longer than 20 bytes is truncated.
+pub fn peer_id_from_seed(prefix: &[u8], seed: u64) -> [u8; 20] {
+    let mut peer_id = [0u8; 20];
+    let prefix_len = prefix.len().min(peer_id.len());
+    peer_id[..prefix_len].copy_from_slice(&prefix[..prefix_len]);
+
+    let digest = Sha1::digest(seed.to_be_bytes());
+    peer_id[prefix_len..].copy_from_slice(&digest[..20 - prefix_len]);
+
+    peer_id
+}
+
+/// Suggested maximum number of peer connections for a link speed
+///
+/// `bandwidth_bps` is the available bandwidth in bits per second. Budgets
+/// about 64 kbit/s per connection, clamped between [`MIN_CONNECTIONS`] and
+/// [`MAX_CONNECTIONS`] so slow links still have peers to choose from and
+/// fast ones don't drown in connection overhead.
+pub fn recommended_connections(bandwidth_bps: u64) -> usize {
+    let connections = bandwidth_bps / BPS_PER_CONNECTION;
+    usize::try_from(connections)
+        .unwrap_or(MAX_CONNECTIONS)
+        .clamp(MIN_CONNECTIONS, MAX_CONNECTIONS)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -29,4 +79,29 @@ mod tests {
         assert_eq!(peer_id.len(), 20);
         assert_eq!(&peer_id[0..8], b"-RS0100-");
     }
+
+    #[test]
+    fn test_peer_id_from_seed() {
+        let peer_id = peer_id_from_seed(b"-RS0100-", 42);
+        assert_eq!(&peer_id[0..8], b"-RS0100-");
+        // Pinned, so IDs stay stable across releases
+        assert_eq!(hex::encode(&peer_id[8..]), "acc8ab6be9a9c010ef66cf0e");
+        assert_eq!(peer_id, peer_id_from_seed(b"-RS0100-", 42));
+        assert_ne!(peer_id, peer_id_from_seed(b"-RS0100-", 43));
+
+        let long_prefix = [b'x'; 24];
+        assert_eq!(peer_id_from_seed(&long_prefix, 42), [b'x'; 20]);
+    }
+
+    #[test]
+    fn test_recommended_connections() {
+        // Dial-up and below get the floor
+        assert_eq!(recommended_connections(0), MIN_CONNECTIONS);
+        assert_eq!(recommended_connections(56_000), MIN_CONNECTIONS);
+        // 2 Mbit/s
+        assert_eq!(recommended_connections(2_000_000), 31);
+        // Gigabit and beyond hit the ceiling
+        assert_eq!(recommended_connections(1_000_000_000), MAX_CONNECTIONS);
+        assert_eq!(recommended_connections(u64::MAX), MAX_CONNECTIONS);
+    }
 }
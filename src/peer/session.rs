@@ -0,0 +1,162 @@
+//! Connected peer sessions
+//!
+//! A session is a TCP connection to a peer that has completed the BitTorrent
+//! handshake for a given torrent.
+
+use super::{HANDSHAKE_LEN, Handshake};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+
+/// How long to wait for a peer to connect and answer the handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A peer connection that has completed the handshake
+#[derive(Debug)]
+pub struct PeerSession {
+    addr: SocketAddr,
+    peer_id: [u8; 20],
+    stream: TcpStream,
+}
+
+impl PeerSession {
+    /// Connect to a peer and exchange handshakes, failing after [`HANDSHAKE_TIMEOUT`]
+    pub async fn connect(
+        addr: SocketAddr,
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+    ) -> crate::Result<Self> {
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, Self::handshake(addr, info_hash, peer_id))
+            .await
+            .map_err(|_| crate::Error::Peer(format!("Handshake with {} timed out", addr)))?
+    }
+
+    async fn handshake(
+        addr: SocketAddr,
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+    ) -> crate::Result<Self> {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(&Handshake::new(info_hash, peer_id).to_bytes())
+            .await?;
+
+        let mut reply = [0u8; HANDSHAKE_LEN];
+        stream.read_exact(&mut reply).await?;
+
+        let reply = Handshake::from_bytes(&reply)?;
+        reply.check_info_hash(&info_hash)?;
+
+        tracing::debug!("Handshake with {} succeeded", addr);
+
+        Ok(Self {
+            addr,
+            peer_id: reply.peer_id,
+            stream,
+        })
+    }
+
+    /// Address of the remote peer
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Peer ID the remote peer sent in its handshake
+    pub fn peer_id(&self) -> &[u8; 20] {
+        &self.peer_id
+    }
+
+    /// Underlying connection, positioned right after the handshake
+    pub fn stream(&mut self) -> &mut TcpStream {
+        &mut self.stream
+    }
+}
+
+/// Handshake with many peers concurrently
+///
+/// At most `concurrency` connections are in flight at once, and each one is
+/// bounded by [`HANDSHAKE_TIMEOUT`]. Results are returned in the same order
+/// as `addrs`.
+pub async fn handshake_all(
+    addrs: Vec<SocketAddr>,
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    concurrency: usize,
+) -> Vec<crate::Result<PeerSession>> {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+
+    let tasks: Vec<_> = addrs
+        .into_iter()
+        .map(|addr| {
+            let permits = Arc::clone(&permits);
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                PeerSession::connect(addr, info_hash, peer_id).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(
+            task.await.unwrap_or_else(|e| {
+                Err(crate::Error::Peer(format!("Handshake task failed: {}", e)))
+            }),
+        );
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Accept one connection and answer its handshake with `info_hash`
+    async fn mock_peer(info_hash: [u8; 20]) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; HANDSHAKE_LEN];
+            socket.read_exact(&mut request).await.unwrap();
+            socket
+                .write_all(&Handshake::new(info_hash, *b"-MOCK00-abcdefghijkl").to_bytes())
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    /// An address nothing is listening on
+    async fn dead_addr() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handshake_all_mixed_peers() {
+        let info_hash = [7u8; 20];
+        let peer_id = [1u8; 20];
+
+        let addrs = vec![
+            mock_peer(info_hash).await,
+            dead_addr().await,
+            mock_peer([9u8; 20]).await,
+            mock_peer(info_hash).await,
+        ];
+
+        let results = handshake_all(addrs.clone(), info_hash, peer_id, 2).await;
+
+        assert_eq!(results.len(), 4);
+        let session = results[0].as_ref().unwrap();
+        assert_eq!(session.addr(), addrs[0]);
+        assert_eq!(session.peer_id(), b"-MOCK00-abcdefghijkl");
+        assert!(matches!(results[1], Err(crate::Error::Io(_))));
+        assert!(matches!(results[2], Err(crate::Error::Handshake(_))));
+        assert!(results[3].is_ok());
+    }
+}
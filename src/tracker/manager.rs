@@ -0,0 +1,205 @@
+//! Multi-tracker failover (BEP 12)
+//!
+//! The trackers of an `announce-list` are grouped in tiers. Each tier is
+//! shuffled once, then tried front to back; a later tier is only used when
+//! every tracker in the earlier ones failed. A tracker that answers is moved
+//! to the front of its tier so it is tried first next time.
+
+use super::{Tracker, TrackerRequest, TrackerResponse};
+use crate::Metainfo;
+use rand::seq::SliceRandom;
+
+/// Announces through tiers of trackers, failing over between them
+pub struct TrackerManager {
+    tiers: Vec<Vec<Tracker>>,
+    /// Private torrents (BEP 27) only announce to their own trackers
+    private: bool,
+}
+
+impl TrackerManager {
+    /// Build from tiers of announce URLs, shuffling each tier
+    ///
+    /// Empty tiers are dropped.
+    pub fn new(tiers: Vec<Vec<String>>) -> Self {
+        let mut rng = rand::thread_rng();
+        let tiers = tiers
+            .into_iter()
+            .filter(|tier| !tier.is_empty())
+            .map(|mut tier| {
+                tier.shuffle(&mut rng);
+                tier.into_iter().map(Tracker::new).collect()
+            })
+            .collect();
+        Self {
+            tiers,
+            private: false,
+        }
+    }
+
+    /// Tiers from the torrent's `announce-list`, or its primary tracker as
+    /// the only tier when it has none (and no tiers for trackerless torrents)
+    ///
+    /// A private torrent's manager refuses [`TrackerManager::add_tracker`].
+    pub fn from_metainfo(metainfo: &Metainfo) -> Self {
+        let mut manager = if metainfo.announce_list.is_empty() {
+            Self::new(vec![metainfo.announce.iter().cloned().collect()])
+        } else {
+            Self::new(metainfo.announce_list.clone())
+        };
+        manager.private = metainfo.is_private();
+        manager
+    }
+
+    /// Add a tracker from outside the torrent (a magnet link, the user) as
+    /// a new last tier
+    ///
+    /// Fails for private torrents, whose peers must only come from the
+    /// trackers listed in the torrent itself.
+    pub fn add_tracker(&mut self, url: String) -> crate::Result<()> {
+        if self.private {
+            return Err(crate::Error::Tracker(format!(
+                "Refusing to add tracker {} to a private torrent",
+                url
+            )));
+        }
+        self.tiers.push(vec![Tracker::new(url)]);
+        Ok(())
+    }
+
+    /// Announce URLs in the order they will next be tried
+    pub fn tiers(&self) -> Vec<Vec<String>> {
+        self.tiers
+            .iter()
+            .map(|tier| {
+                tier.iter()
+                    .map(|tracker| tracker.announce_url.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Announce to the first tracker that answers
+    ///
+    /// If none does, the error lists each tracker's failure in the order
+    /// they were tried.
+    pub fn announce(&mut self, request: &TrackerRequest) -> crate::Result<TrackerResponse> {
+        let mut failures = Vec::new();
+        for tier in &mut self.tiers {
+            for i in 0..tier.len() {
+                match tier[i].announce(request) {
+                    Ok(response) => {
+                        let tracker = tier.remove(i);
+                        tier.insert(0, tracker);
+                        return Ok(response);
+                    }
+                    Err(e) => {
+                        tracing::debug!("Tracker {} failed: {}", tier[i].announce_url, e);
+                        failures.push(format!("{}: {}", tier[i].announce_url, e));
+                    }
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            return Err(crate::Error::Tracker(
+                "No trackers to announce to".to_string(),
+            ));
+        }
+        Err(crate::Error::Tracker(format!(
+            "All trackers failed: {}",
+            failures.join("; ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::mock::MockTracker;
+
+    fn request() -> TrackerRequest {
+        TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000)
+    }
+
+    fn failing() -> MockTracker {
+        MockTracker::with_response(b"d14:failure reason4:downe".to_vec())
+    }
+
+    #[test]
+    fn test_fails_over_and_promotes() {
+        let (down_a, down_b, up) = (failing(), failing(), MockTracker::start());
+        let mut manager = TrackerManager::new(vec![
+            vec![down_a.announce_url()],
+            vec![down_b.announce_url(), up.announce_url()],
+        ]);
+
+        manager.announce(&request()).unwrap();
+        assert_eq!(down_a.requests().len(), 1);
+        assert_eq!(up.requests().len(), 1);
+        assert_eq!(manager.tiers()[1][0], up.announce_url());
+
+        // The working tracker is now tried first within its tier
+        let tried = down_b.requests().len();
+        manager.announce(&request()).unwrap();
+        assert_eq!(down_b.requests().len(), tried);
+        assert_eq!(up.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_later_tier_unused_on_success() {
+        let (first, second) = (MockTracker::start(), MockTracker::start());
+        let mut manager = TrackerManager::new(vec![
+            vec![first.announce_url()],
+            vec![second.announce_url()],
+        ]);
+
+        manager.announce(&request()).unwrap();
+        assert_eq!(first.requests().len(), 1);
+        assert!(second.requests().is_empty());
+    }
+
+    #[test]
+    fn test_private_torrent_refuses_extra_trackers() {
+        let torrent = "d8:announce21:http://tracker.test/a4:infod6:lengthi1000e4:name4:test\
+                       12:piece lengthi512e6:pieces20:123456789012345678907:privatei1eee";
+        let private = Metainfo::from_bytes(torrent.as_bytes()).unwrap();
+        let mut manager = TrackerManager::from_metainfo(&private);
+        assert!(
+            manager
+                .add_tracker("http://other.test/announce".to_string())
+                .is_err()
+        );
+        assert_eq!(manager.tiers(), vec![vec!["http://tracker.test/a"]]);
+
+        let public = Metainfo::from_bytes(torrent.replace("7:privatei1e", "").as_bytes()).unwrap();
+        let mut manager = TrackerManager::from_metainfo(&public);
+        manager
+            .add_tracker("http://other.test/announce".to_string())
+            .unwrap();
+        assert_eq!(
+            manager.tiers(),
+            vec![
+                vec!["http://tracker.test/a"],
+                vec!["http://other.test/announce"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_trackers_fail() {
+        let (a, b) = (failing(), failing());
+        let mut manager = TrackerManager::new(vec![vec![a.announce_url()], vec![b.announce_url()]]);
+
+        let Err(crate::Error::Tracker(message)) = manager.announce(&request()) else {
+            panic!("expected a tracker error");
+        };
+        assert!(message.contains(&a.announce_url()), "{}", message);
+        assert!(message.contains(&b.announce_url()), "{}", message);
+
+        assert!(
+            TrackerManager::new(vec![vec![]])
+                .announce(&request())
+                .is_err()
+        );
+    }
+}
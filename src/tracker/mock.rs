@@ -0,0 +1,172 @@
+//! A minimal HTTP tracker for tests
+//!
+//! [`MockTracker`] listens on a local port, answers every announce with a
+//! configurable bencoded body and records the requests it receives. It is
+//! available to downstream crates with the `test-util` feature.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Body served until [`MockTracker::set_response`] is called
+const DEFAULT_RESPONSE: &[u8] = b"d8:intervali1800e5:peers0:e";
+
+/// Local HTTP tracker serving a fixed announce response
+pub struct MockTracker {
+    addr: SocketAddr,
+    state: Arc<State>,
+}
+
+struct State {
+    response: Mutex<Vec<u8>>,
+    /// Requests still to be failed, and the status to fail them with
+    failures: Mutex<(usize, u16)>,
+    requests: Mutex<Vec<String>>,
+    connections: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+impl MockTracker {
+    /// Start listening on an ephemeral local port
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock tracker");
+        let addr = listener.local_addr().expect("mock tracker address");
+        let state = Arc::new(State {
+            response: Mutex::new(DEFAULT_RESPONSE.to_vec()),
+            failures: Mutex::new((0, 200)),
+            requests: Mutex::new(Vec::new()),
+            connections: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        });
+
+        let accept_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_state.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                accept_state.connections.fetch_add(1, Ordering::SeqCst);
+                let state = Arc::clone(&accept_state);
+                thread::spawn(move || serve_connection(stream, &state));
+            }
+        });
+
+        Self { addr, state }
+    }
+
+    /// Start with `body` as the announce response
+    pub fn with_response(body: impl Into<Vec<u8>>) -> Self {
+        let tracker = Self::start();
+        tracker.set_response(body);
+        tracker
+    }
+
+    /// Replace the body served to subsequent announces
+    pub fn set_response(&self, body: impl Into<Vec<u8>>) {
+        *self.state.response.lock().unwrap() = body.into();
+    }
+
+    /// Answer the next `count` requests with HTTP `status` and an empty
+    /// body, then go back to the configured response
+    pub fn fail_next(&self, count: usize, status: u16) {
+        *self.state.failures.lock().unwrap() = (count, status);
+    }
+
+    /// URL to announce to
+    pub fn announce_url(&self) -> String {
+        format!("http://{}/announce", self.addr)
+    }
+
+    /// Request targets received so far (path and query, such as
+    /// `/announce?info_hash=...`), in arrival order
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Number of TCP connections accepted so far
+    pub fn connections(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockTracker {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it notices the flag
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// Answer requests on one connection until the client closes it
+fn serve_connection(mut stream: TcpStream, state: &State) {
+    let mut pending = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    loop {
+        let Some(end) = pending.windows(4).position(|w| w == b"\r\n\r\n") else {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => pending.extend_from_slice(&buf[..n]),
+            }
+            continue;
+        };
+
+        // Announces are GET requests, so there is no body to skip
+        let head = String::from_utf8_lossy(&pending[..end]).into_owned();
+        pending.drain(..end + 4);
+        let target = head
+            .lines()
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .unwrap_or_default();
+        state.requests.lock().unwrap().push(target.to_string());
+
+        let (status, body) = {
+            let mut failures = state.failures.lock().unwrap();
+            if failures.0 > 0 {
+                failures.0 -= 1;
+                (failures.1, Vec::new())
+            } else {
+                (200, state.response.lock().unwrap().clone())
+            }
+        };
+        let header = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\n\r\n",
+            status,
+            body.len()
+        );
+        if stream.write_all(header.as_bytes()).is_err() || stream.write_all(&body).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::{Tracker, TrackerRequest};
+
+    #[test]
+    fn test_drives_announce() {
+        let mock = MockTracker::with_response(
+            b"d8:intervali60e5:peers6:\x0a\x00\x00\x01\x1a\xe1e".to_vec(),
+        );
+        let tracker = Tracker::new(mock.announce_url());
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        let response = tracker.announce(&request).unwrap();
+        assert_eq!(response.interval, 60);
+        assert_eq!(response.peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+
+        mock.set_response(b"d8:intervali120e5:peers0:e".to_vec());
+        assert_eq!(tracker.announce(&request).unwrap().interval, 120);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("/announce?"));
+        assert!(requests[0].contains("event=started"));
+    }
+}
@@ -3,13 +3,37 @@
 //! Trackers help peers find each other. The client announces its presence
 //! and receives a list of peers that have the same torrent.
 
+mod manager;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+mod retry;
+mod scrape;
+mod session;
+mod udp;
+
+pub use manager::TrackerManager;
+pub use retry::{RetryPolicy, is_retryable};
+pub use scrape::ScrapeStats;
+pub use session::{MAX_NUMWANT, TrackerSession};
+pub use udp::UdpTimeouts;
+
+use crate::Metainfo;
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use rand::Rng;
 use serde::Deserialize;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::time::Duration;
 use url::Url;
 
-/// HTTP tracker client
+/// Tracker client for `http(s)://` and `udp://` announce URLs
+#[derive(Clone)]
 pub struct Tracker {
     announce_url: String,
+    strict_peers: bool,
+    udp_timeouts: UdpTimeouts,
+    retry: RetryPolicy,
 }
 
 /// Request sent to tracker
@@ -31,6 +55,34 @@ pub struct TrackerRequest {
     pub compact: bool,
     /// Event (started, stopped, completed)
     pub event: Option<TrackerEvent>,
+    /// Random value that stays fixed for this client across announces, so
+    /// the tracker can recognize us after an IP change
+    pub key: Option<String>,
+    /// Advertise that we support Message Stream Encryption
+    ///
+    /// This only signals capability to the swarm; the crate does not
+    /// implement MSE itself yet.
+    pub supportcrypto: bool,
+    /// Ask for peers that accept encrypted connections only
+    ///
+    /// Like `supportcrypto`, this is a signal only: MSE is not implemented.
+    pub requirecrypto: bool,
+    /// Leave `uploaded` and `downloaded` out of the announce, for trackers
+    /// that reject them on some announces (typically `stopped`)
+    pub omit_transfer_stats: bool,
+    /// Number of peers to ask for; `None` lets the tracker decide
+    pub numwant: Option<u32>,
+    /// Ask the tracker to leave peer IDs out of a non-compact peer list
+    pub no_peer_id: bool,
+    /// `tracker id` from the tracker's previous response, echoed back as
+    /// `trackerid`
+    pub tracker_id: Option<String>,
+    /// Our IPv4 address (`ipv4`, BEP 7), for dual-stack clients announcing
+    /// over IPv6
+    pub ipv4: Option<Ipv4Addr>,
+    /// Our IPv6 address and the port listening on it (`ipv6`, BEP 7), for
+    /// dual-stack clients announcing over IPv4
+    pub ipv6: Option<SocketAddrV6>,
 }
 
 #[derive(Debug, Clone)]
@@ -45,78 +97,474 @@ pub enum TrackerEvent {
 pub struct TrackerResponse {
     /// Interval to wait before announcing again (seconds)
     pub interval: u32,
+    /// Shortest interval the tracker allows between announces (seconds)
+    pub min_interval: Option<u32>,
     /// List of peer addresses
     pub peers: Vec<SocketAddr>,
+    /// Peers the dictionary peer format gave by hostname, as `(host, port)`
+    ///
+    /// These are left unresolved so parsing never waits on DNS; see
+    /// [`TrackerResponse::resolve_peer_hosts`].
+    pub peer_hosts: Vec<(String, u16)>,
     /// Number of seeders (optional)
     pub seeders: Option<u32>,
     /// Number of leechers (optional)
     pub leechers: Option<u32>,
+    /// Non-fatal `warning message` sent alongside a normal response
+    pub warning: Option<String>,
+    /// Opaque `tracker id` to send back as `trackerid` on later announces
+    pub tracker_id: Option<String>,
+}
+
+/// Bytes exchanged for one announce, for bandwidth accounting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnnounceStats {
+    /// Length of the announce URL sent, or of the announce packet for
+    /// `udp://` trackers
+    pub request_url_len: usize,
+    /// Length of the response body received, or of the reply packet
+    pub response_body_len: usize,
+}
+
+impl AnnounceStats {
+    /// Log the sizes at debug level, as every announce does
+    fn log(&self) {
+        tracing::debug!(
+            request_url_len = self.request_url_len,
+            response_body_len = self.response_body_len,
+            "Announce sent {} bytes of request, received {} bytes",
+            self.request_url_len,
+            self.response_body_len
+        );
+    }
+}
+
+impl TrackerResponse {
+    /// How long to wait before the next announce
+    ///
+    /// The `interval` with ±10% random jitter, so clients that started
+    /// together don't keep announcing in lockstep. Never shorter than
+    /// `min_interval`.
+    pub fn next_announce_delay(&self) -> Duration {
+        let interval = Duration::from_secs(self.interval.into());
+        let jittered = interval.mul_f64(rand::thread_rng().gen_range(0.9..=1.1));
+        let floor = Duration::from_secs(self.min_interval.unwrap_or(0).into());
+        jittered.max(floor)
+    }
+
+    /// Resolve [`TrackerResponse::peer_hosts`], skipping hosts that don't
+    /// resolve
+    ///
+    /// Blocks on a DNS lookup per host, so call it off any latency-sensitive
+    /// path.
+    pub fn resolve_peer_hosts(&self) -> Vec<SocketAddr> {
+        self.peer_hosts
+            .iter()
+            .filter_map(|(host, port)| {
+                let resolved = (host.as_str(), *port)
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next());
+                if resolved.is_none() {
+                    tracing::debug!("Skipping peer with unresolvable host {}", host);
+                }
+                resolved
+            })
+            .collect()
+    }
+
+    /// Like [`TrackerResponse::resolve_peer_hosts`], but resolving through
+    /// tokio so the runtime isn't blocked
+    #[cfg(feature = "async")]
+    pub async fn resolve_peer_hosts_async(&self) -> Vec<SocketAddr> {
+        let mut peers = Vec::new();
+        for (host, port) in &self.peer_hosts {
+            match tokio::net::lookup_host((host.as_str(), *port)).await {
+                Ok(mut addrs) => peers.extend(addrs.next()),
+                Err(e) => tracing::debug!("Skipping peer with unresolvable host {}: {}", host, e),
+            }
+        }
+        peers
+    }
+
+    /// Peers with the complete torrent (`complete`), or 0 if not reported
+    pub fn seeder_count(&self) -> u32 {
+        self.seeders.unwrap_or(0)
+    }
+
+    /// Peers still downloading (`incomplete`), or 0 if not reported
+    pub fn leecher_count(&self) -> u32 {
+        self.leechers.unwrap_or(0)
+    }
+
+    /// Total peers in the swarm according to the tracker
+    ///
+    /// The sum of both counts when the tracker reports either of them.
+    /// Otherwise falls back to the number of peers returned (hostname ones
+    /// included), which is a lower bound since trackers cap the peer list.
+    pub fn swarm_size(&self) -> u32 {
+        if self.seeders.is_none() && self.leechers.is_none() {
+            return (self.peers.len() + self.peer_hosts.len()) as u32;
+        }
+        self.seeder_count().saturating_add(self.leecher_count())
+    }
 }
 
 #[derive(Deserialize)]
 struct BencodeTrackerResponse {
-    interval: i64,
+    interval: LenientInt,
+    #[serde(rename = "min interval", default)]
+    min_interval: Option<LenientInt>,
     #[serde(default)]
-    complete: Option<i64>,
+    complete: Option<LenientInt>,
     #[serde(default)]
-    incomplete: Option<i64>,
+    incomplete: Option<LenientInt>,
+    #[serde(rename = "warning message", default, with = "serde_bytes")]
+    warning: Option<Vec<u8>>,
+    #[serde(rename = "tracker id", default, with = "serde_bytes")]
+    tracker_id: Option<Vec<u8>>,
+    peers: PeersField,
+    #[serde(default, with = "serde_bytes")]
+    peers6: Vec<u8>,
+}
+
+/// Just the `failure reason` of a response, checked before anything else
+/// since a failed announce carries none of the other fields
+#[derive(Deserialize)]
+struct BencodeFailure {
+    #[serde(rename = "failure reason", default, with = "serde_bytes")]
+    failure_reason: Option<Vec<u8>>,
+}
+
+/// An integer that some trackers send as a numeric string instead
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LenientInt {
+    Int(i64),
+    Text(#[serde(with = "serde_bytes")] Vec<u8>),
+}
+
+impl LenientInt {
+    fn value(&self, field: &str) -> crate::Result<i64> {
+        match self {
+            LenientInt::Int(n) => Ok(*n),
+            LenientInt::Text(text) => std::str::from_utf8(text)
+                .ok()
+                .and_then(|text| text.trim().parse().ok())
+                .ok_or_else(|| {
+                    crate::Error::Tracker(format!(
+                        "`{}` is not a number: {}",
+                        field,
+                        String::from_utf8_lossy(text)
+                    ))
+                }),
+        }
+    }
+}
+
+/// Turn a 5xx status into an error so it can be retried; other statuses
+/// are left for the body to explain
+///
+/// Takes the response's status and `error_for_status_ref` result, so it
+/// serves both the blocking and the async client.
+fn reject_server_error<T>(
+    status: reqwest::StatusCode,
+    checked: reqwest::Result<T>,
+) -> crate::Result<()> {
+    match checked {
+        Err(e) if status.is_server_error() => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn optional_int(value: &Option<LenientInt>, field: &str) -> crate::Result<Option<u32>> {
+    value
+        .as_ref()
+        .map(|n| n.value(field).map(|n| n as u32))
+        .transpose()
+}
+
+/// `peers` is either a compact string or a list of dictionaries
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PeersField {
+    Compact(#[serde(with = "serde_bytes")] Vec<u8>),
+    Dicts(Vec<DictPeer>),
+}
+
+/// One entry of the dictionary peer format; `peer id` is accepted but unused
+#[derive(Deserialize)]
+struct DictPeer {
     #[serde(with = "serde_bytes")]
-    peers: Vec<u8>,
+    ip: Vec<u8>,
+    port: i64,
 }
 
 impl Tracker {
     /// Create a new tracker client
     pub fn new(announce_url: String) -> Self {
-        Self { announce_url }
+        Self {
+            announce_url,
+            strict_peers: false,
+            udp_timeouts: UdpTimeouts::default(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Try HTTP announces up to `attempts` times in all, waiting
+    /// `base_delay` before the first retry and twice as long before each
+    /// following one (at most a minute)
+    ///
+    /// Only transient failures are retried; see [`is_retryable`].
+    pub fn with_retry(self, attempts: u32, base_delay: Duration) -> Self {
+        self.with_retry_policy(RetryPolicy {
+            attempts,
+            base_delay,
+            ..RetryPolicy::default()
+        })
+    }
+
+    /// Retry HTTP announces according to `policy`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Retransmission schedule for `udp://` trackers (BEP 15 by default)
+    pub fn with_udp_timeouts(mut self, timeouts: UdpTimeouts) -> Self {
+        self.udp_timeouts = timeouts;
+        self
+    }
+
+    /// Reject compact peer lists whose length isn't a multiple of 6
+    ///
+    /// By default a trailing partial entry, as sent by some buggy trackers,
+    /// is dropped with a warning and the complete entries are kept.
+    pub fn with_strict_peers(mut self, strict: bool) -> Self {
+        self.strict_peers = strict;
+        self
     }
 
     /// Announce to tracker and get peer list
     pub fn announce(&self, request: &TrackerRequest) -> crate::Result<TrackerResponse> {
+        self.announce_raw(request).map(|(response, _)| response)
+    }
+
+    /// Like [`Tracker::announce`], but also return the raw response body
+    ///
+    /// Useful for debugging or caching, and for reading keys that
+    /// [`TrackerResponse`] does not keep.
+    pub fn announce_raw(
+        &self,
+        request: &TrackerRequest,
+    ) -> crate::Result<(TrackerResponse, Vec<u8>)> {
+        self.announce_with(&reqwest::blocking::Client::new(), request)
+            .map(|(response, body, _)| (response, body))
+    }
+
+    /// Like [`Tracker::announce`], but also report how many bytes it took
+    ///
+    /// Every announce logs the same sizes at debug level.
+    pub fn announce_with_stats(
+        &self,
+        request: &TrackerRequest,
+    ) -> crate::Result<(TrackerResponse, AnnounceStats)> {
+        self.announce_with(&reqwest::blocking::Client::new(), request)
+            .map(|(response, _, stats)| (response, stats))
+    }
+
+    /// Announce several torrents in turn over one HTTP client
+    ///
+    /// HTTP trackers have no batch announce, but sharing the client lets
+    /// the connection be kept alive between requests. Results are in the
+    /// same order as `requests`.
+    pub fn announce_many(
+        &self,
+        requests: &[TrackerRequest],
+    ) -> Vec<crate::Result<TrackerResponse>> {
+        let client = reqwest::blocking::Client::new();
+        requests
+            .iter()
+            .map(|request| {
+                self.announce_with(&client, request)
+                    .map(|(response, _, _)| response)
+            })
+            .collect()
+    }
+
+    fn announce_with(
+        &self,
+        client: &reqwest::blocking::Client,
+        request: &TrackerRequest,
+    ) -> crate::Result<(TrackerResponse, Vec<u8>, AnnounceStats)> {
+        if self.is_udp() {
+            return udp::announce(self, request);
+        }
+
+        let url = self.build_url(request)?;
+
+        tracing::debug!("Announcing to tracker: {}", url);
+
+        let body = self.retry.run(|| {
+            let response = client.get(&url).send()?;
+            reject_server_error(response.status(), response.error_for_status_ref())?;
+            Ok(response.bytes()?.to_vec())
+        })?;
+        self.finish_announce(&url, body)
+    }
+
+    /// Like [`Tracker::announce`], but without blocking the calling thread,
+    /// for use inside an async runtime
+    ///
+    /// `udp://` trackers still use blocking sockets, so they are announced to
+    /// on tokio's blocking thread pool. Hostname peers are left in
+    /// [`TrackerResponse::peer_hosts`]; resolve them with
+    /// [`TrackerResponse::resolve_peer_hosts_async`].
+    #[cfg(feature = "async")]
+    pub async fn announce_async(&self, request: &TrackerRequest) -> crate::Result<TrackerResponse> {
+        if self.is_udp() {
+            let (tracker, request) = (self.clone(), request.clone());
+            let (response, _, _) =
+                tokio::task::spawn_blocking(move || udp::announce(&tracker, &request))
+                    .await
+                    .map_err(|e| {
+                        crate::Error::Tracker(format!("UDP announce task failed: {}", e))
+                    })??;
+            return Ok(response);
+        }
+
         let url = self.build_url(request)?;
 
         tracing::debug!("Announcing to tracker: {}", url);
 
-        let response = reqwest::blocking::get(&url)?;
-        let body = response.bytes()?;
+        let (client, url) = (&reqwest::Client::new(), &url);
+        let body = self
+            .retry
+            .run_async(move || async move {
+                let response = client.get(url).send().await?;
+                reject_server_error(response.status(), response.error_for_status_ref())?;
+                Ok(response.bytes().await?.to_vec())
+            })
+            .await?;
+        self.finish_announce(url, body)
+            .map(|(response, _, _)| response)
+    }
+
+    /// Log the sizes of a completed HTTP announce and parse its body
+    fn finish_announce(
+        &self,
+        url: &str,
+        body: Vec<u8>,
+    ) -> crate::Result<(TrackerResponse, Vec<u8>, AnnounceStats)> {
+        let stats = AnnounceStats {
+            request_url_len: url.len(),
+            response_body_len: body.len(),
+        };
+        stats.log();
+
+        let parsed = self.parse_response(&body)?;
+        Ok((parsed, body, stats))
+    }
+
+    fn parse_response(&self, body: &[u8]) -> crate::Result<TrackerResponse> {
+        // Some trackers answer errors with plain text and a 200 status
+        if !matches!(body.first(), Some(b'd' | b'l' | b'i' | b'0'..=b'9')) {
+            return Err(crate::Error::Tracker(format!(
+                "Tracker returned a non-bencoded response: {}",
+                String::from_utf8_lossy(body).trim()
+            )));
+        }
 
         // Parse bencode response
+        let body = unwrap_double_encoded(body);
+        check_failure(&body)?;
         let tracker_response: BencodeTrackerResponse =
             serde_bencode::from_bytes(&body).map_err(|e| crate::Error::Tracker(e.to_string()))?;
 
-        // Parse compact peer format
-        let peers = Self::parse_compact_peers(&tracker_response.peers)?;
+        let (mut peers, peer_hosts) = match &tracker_response.peers {
+            PeersField::Compact(data) => (
+                Self::parse_compact_peers(data, self.strict_peers)?,
+                Vec::new(),
+            ),
+            PeersField::Dicts(dicts) => Self::parse_dict_peers(dicts),
+        };
+        peers.extend(Self::parse_compact_peers6(&tracker_response.peers6)?);
 
         tracing::info!("Received {} peers from tracker", peers.len());
 
+        let warning = tracker_response
+            .warning
+            .map(|w| String::from_utf8_lossy(&w).into_owned());
+        if let Some(warning) = &warning {
+            tracing::warn!("Tracker warning: {}", warning);
+        }
+
         Ok(TrackerResponse {
-            interval: tracker_response.interval as u32,
+            interval: tracker_response.interval.value("interval")? as u32,
+            min_interval: optional_int(&tracker_response.min_interval, "min interval")?,
             peers,
-            seeders: tracker_response.complete.map(|n| n as u32),
-            leechers: tracker_response.incomplete.map(|n| n as u32),
+            peer_hosts,
+            seeders: optional_int(&tracker_response.complete, "complete")?,
+            leechers: optional_int(&tracker_response.incomplete, "incomplete")?,
+            warning,
+            tracker_id: tracker_response
+                .tracker_id
+                .map(|id| String::from_utf8_lossy(&id).into_owned()),
         })
     }
 
+    /// Filesystem-safe identifier for this tracker, for naming per-tracker
+    /// state files (such as a persisted `key` or tracker id)
+    ///
+    /// This is the hex SHA-1 of the announce URL, so it is stable across runs.
+    pub fn state_key(&self) -> String {
+        hex::encode(Sha1::digest(self.announce_url.as_bytes()))
+    }
+
+    /// Check whether the tracker answers at all within `timeout`
+    ///
+    /// Any HTTP response counts, even an error status or a failure reason,
+    /// since it shows the tracker is up.
+    pub fn is_alive(&self, timeout: Duration) -> bool {
+        if self.is_udp() {
+            return udp::is_alive(&self.announce_url, timeout);
+        }
+        let client = match reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()
+        {
+            Ok(client) => client,
+            Err(_) => return false,
+        };
+        match client.get(&self.announce_url).send() {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::debug!("Tracker {} is unreachable: {}", self.announce_url, e);
+                false
+            }
+        }
+    }
+
+    fn is_udp(&self) -> bool {
+        self.announce_url.starts_with("udp://")
+    }
+
+    /// Build the announce URL, keeping any query the announce URL already
+    /// has (such as a private tracker passkey) in front
     fn build_url(&self, req: &TrackerRequest) -> crate::Result<String> {
         let mut url = Url::parse(&self.announce_url)?;
 
-        // Add query parameters
-        url.query_pairs_mut()
-            .append_pair("info_hash", &Self::url_encode_bytes(&req.info_hash))
-            .append_pair("peer_id", &Self::url_encode_bytes(&req.peer_id))
-            .append_pair("port", &req.port.to_string())
-            .append_pair("uploaded", &req.uploaded.to_string())
-            .append_pair("downloaded", &req.downloaded.to_string())
-            .append_pair("left", &req.left.to_string())
-            .append_pair("compact", if req.compact { "1" } else { "0" });
-
-        if let Some(event) = &req.event {
-            let event_str = match event {
-                TrackerEvent::Started => "started",
-                TrackerEvent::Stopped => "stopped",
-                TrackerEvent::Completed => "completed",
-            };
-            url.query_pairs_mut().append_pair("event", event_str);
-        }
+        let announce_query = req
+            .to_query_pairs()
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        let query = match url.query() {
+            Some(existing) if !existing.is_empty() => format!("{}&{}", existing, announce_query),
+            _ => announce_query,
+        };
+        url.set_query(Some(&query));
 
         Ok(url.to_string())
     }
@@ -126,14 +574,51 @@ impl Tracker {
         bytes.iter().map(|&b| format!("%{:02x}", b)).collect()
     }
 
+    /// Parse the dictionary peer format into IP-literal peers and
+    /// unresolved `(host, port)` pairs
+    ///
+    /// `ip` may be an IPv4 address, a bare or bracketed IPv6 address, or a
+    /// hostname. Hostnames are not resolved here, so a slow resolver can't
+    /// stall the announce. Entries with an invalid port or empty `ip` are
+    /// skipped.
+    fn parse_dict_peers(dicts: &[DictPeer]) -> (Vec<SocketAddr>, Vec<(String, u16)>) {
+        let mut peers = Vec::new();
+        let mut hosts = Vec::new();
+        for peer in dicts {
+            let ip = String::from_utf8_lossy(&peer.ip);
+            let Ok(port) = u16::try_from(peer.port) else {
+                tracing::debug!("Skipping peer {} with invalid port {}", ip, peer.port);
+                continue;
+            };
+            let host = ip
+                .strip_prefix('[')
+                .and_then(|h| h.strip_suffix(']'))
+                .unwrap_or(&ip);
+
+            match host.parse::<IpAddr>() {
+                Ok(addr) => peers.push(SocketAddr::new(addr, port)),
+                Err(_) if !host.is_empty() => hosts.push((host.to_string(), port)),
+                Err(_) => tracing::debug!("Skipping peer with empty ip"),
+            }
+        }
+        (peers, hosts)
+    }
+
     /// Parse compact peer format (6 bytes per peer: 4 for IP, 2 for port)
-    fn parse_compact_peers(data: &[u8]) -> crate::Result<Vec<SocketAddr>> {
+    fn parse_compact_peers(data: &[u8], strict: bool) -> crate::Result<Vec<SocketAddr>> {
         const PEER_SIZE: usize = 6;
 
-        if data.len() % PEER_SIZE != 0 {
-            return Err(crate::Error::Tracker(
-                "Invalid compact peer data length".to_string(),
-            ));
+        let trailing = data.len() % PEER_SIZE;
+        if trailing != 0 {
+            if strict {
+                return Err(crate::Error::Tracker(
+                    "Invalid compact peer data length".to_string(),
+                ));
+            }
+            tracing::warn!(
+                "Ignoring {} trailing bytes of truncated compact peer data",
+                trailing
+            );
         }
 
         let peers = data
@@ -147,24 +632,305 @@ impl Tracker {
 
         Ok(peers)
     }
+
+    /// Parse the BEP 7 `peers6` format (18 bytes per peer: 16 for IP, 2 for port)
+    fn parse_compact_peers6(data: &[u8]) -> crate::Result<Vec<SocketAddr>> {
+        const PEER_SIZE: usize = 18;
+
+        if !data.len().is_multiple_of(PEER_SIZE) {
+            return Err(crate::Error::Tracker(
+                "Invalid compact IPv6 peer data length".to_string(),
+            ));
+        }
+
+        let peers = data
+            .chunks_exact(PEER_SIZE)
+            .map(|chunk| {
+                let ip: [u8; 16] = chunk[..16].try_into().unwrap();
+                let port = u16::from_be_bytes([chunk[16], chunk[17]]);
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port)
+            })
+            .collect();
+
+        Ok(peers)
+    }
+}
+
+/// Turn a `failure reason` in a bencoded response into `Error::Tracker`
+fn check_failure(body: &[u8]) -> crate::Result<()> {
+    match serde_bencode::from_bytes::<BencodeFailure>(body) {
+        Ok(BencodeFailure {
+            failure_reason: Some(reason),
+        }) => Err(crate::Error::Tracker(
+            String::from_utf8_lossy(&reason).into_owned(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The response a quirky tracker bencoded a second time, if `body` is a single
+/// byte string holding a dictionary; otherwise `body` itself
+///
+/// Only one layer is removed, so a response can't make this loop.
+fn unwrap_double_encoded(body: &[u8]) -> Cow<'_, [u8]> {
+    match crate::bencode::Value::decode(body) {
+        Ok(crate::bencode::Value::Bytes(inner)) if inner.first() == Some(&b'd') => {
+            tracing::debug!("Unwrapping double-bencoded tracker response");
+            Cow::Owned(inner)
+        }
+        _ => Cow::Borrowed(body),
+    }
 }
 
 impl TrackerRequest {
+    /// Announce parameters, already percent-encoded, in canonical order
+    ///
+    /// Some strict private trackers check the order, so this always follows
+    /// mainline: `info_hash`, `peer_id`, `port`, `uploaded`, `downloaded`,
+    /// `left`, `compact`, `no_peer_id`, `event`, `key`, `numwant`,
+    /// `trackerid`, `ipv4`, `ipv6`, then the crypto flags. Optional
+    /// parameters are left out rather than reordered.
+    pub fn to_query_pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = vec![
+            ("info_hash", Tracker::url_encode_bytes(&self.info_hash)),
+            ("peer_id", Tracker::url_encode_bytes(&self.peer_id)),
+            ("port", self.port.to_string()),
+        ];
+
+        if !self.omit_transfer_stats {
+            pairs.push(("uploaded", self.uploaded.to_string()));
+            pairs.push(("downloaded", self.downloaded.to_string()));
+        }
+
+        pairs.push(("left", self.left.to_string()));
+        pairs.push(("compact", if self.compact { "1" } else { "0" }.to_string()));
+        if self.no_peer_id {
+            pairs.push(("no_peer_id", "1".to_string()));
+        }
+
+        if let Some(event) = &self.event {
+            let event_str = match event {
+                TrackerEvent::Started => "started",
+                TrackerEvent::Stopped => "stopped",
+                TrackerEvent::Completed => "completed",
+            };
+            pairs.push(("event", event_str.to_string()));
+        }
+
+        if let Some(key) = &self.key {
+            pairs.push((
+                "key",
+                utf8_percent_encode(key, NON_ALPHANUMERIC).to_string(),
+            ));
+        }
+
+        if let Some(numwant) = self.numwant {
+            pairs.push(("numwant", numwant.to_string()));
+        }
+
+        if let Some(tracker_id) = &self.tracker_id {
+            pairs.push((
+                "trackerid",
+                utf8_percent_encode(tracker_id, NON_ALPHANUMERIC).to_string(),
+            ));
+        }
+
+        if let Some(ipv4) = self.ipv4 {
+            pairs.push(("ipv4", ipv4.to_string()));
+        }
+        if let Some(ipv6) = self.ipv6 {
+            pairs.push((
+                "ipv6",
+                utf8_percent_encode(&ipv6.to_string(), NON_ALPHANUMERIC).to_string(),
+            ));
+        }
+
+        if self.supportcrypto {
+            pairs.push(("supportcrypto", "1".to_string()));
+        }
+        if self.requirecrypto {
+            pairs.push(("requirecrypto", "1".to_string()));
+        }
+
+        pairs
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    /// Rebuild a request from pairs produced by [`TrackerRequest::to_query_pairs`]
+    ///
+    /// Values are percent-decoded. `info_hash`, `peer_id`, `port` and `left`
+    /// are required; unknown parameters are ignored. A request without
+    /// `uploaded` and `downloaded` comes back with `omit_transfer_stats` set.
+    pub fn from_query_pairs(pairs: &[(String, String)]) -> crate::Result<Self> {
+        let get = |name: &str| {
+            pairs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| percent_decode_str(v).collect::<Vec<u8>>())
+        };
+        let text = |name: &str| get(name).map(|v| String::from_utf8_lossy(&v).into_owned());
+        let required = |name: &str| {
+            text(name).ok_or_else(|| crate::Error::Tracker(format!("missing `{}`", name)))
+        };
+        let id = |name: &str| -> crate::Result<[u8; 20]> {
+            get(name)
+                .ok_or_else(|| crate::Error::Tracker(format!("missing `{}`", name)))?
+                .try_into()
+                .map_err(|_| crate::Error::Tracker(format!("`{}` must be 20 bytes", name)))
+        };
+        fn number<T: std::str::FromStr>(name: &str, value: &str) -> crate::Result<T> {
+            value
+                .parse()
+                .map_err(|_| crate::Error::Tracker(format!("invalid `{}`: {}", name, value)))
+        }
+
+        let uploaded = text("uploaded");
+        let downloaded = text("downloaded");
+        let event = match text("event").as_deref() {
+            None | Some("") => None,
+            Some("started") => Some(TrackerEvent::Started),
+            Some("stopped") => Some(TrackerEvent::Stopped),
+            Some("completed") => Some(TrackerEvent::Completed),
+            Some(other) => {
+                return Err(crate::Error::Tracker(format!("unknown event `{}`", other)));
+            }
+        };
+
+        Ok(Self {
+            info_hash: id("info_hash")?,
+            peer_id: id("peer_id")?,
+            port: number("port", &required("port")?)?,
+            uploaded: uploaded
+                .as_deref()
+                .map_or(Ok(0), |v| number("uploaded", v))?,
+            downloaded: downloaded
+                .as_deref()
+                .map_or(Ok(0), |v| number("downloaded", v))?,
+            left: number("left", &required("left")?)?,
+            compact: text("compact").as_deref() != Some("0"),
+            event,
+            key: text("key"),
+            supportcrypto: text("supportcrypto").as_deref() == Some("1"),
+            requirecrypto: text("requirecrypto").as_deref() == Some("1"),
+            omit_transfer_stats: uploaded.is_none() && downloaded.is_none(),
+            numwant: text("numwant").map(|v| number("numwant", &v)).transpose()?,
+            no_peer_id: text("no_peer_id").as_deref() == Some("1"),
+            tracker_id: text("trackerid"),
+            ipv4: text("ipv4").map(|v| number("ipv4", &v)).transpose()?,
+            // A bare address means the same port as `port`
+            ipv6: match text("ipv6") {
+                Some(v) => Some(match v.parse::<Ipv6Addr>() {
+                    Ok(addr) => SocketAddrV6::new(addr, number("port", &required("port")?)?, 0, 0),
+                    Err(_) => number("ipv6", &v)?,
+                }),
+                None => None,
+            },
+        })
+    }
+
+    /// Create the `started` request for a torrent
+    ///
+    /// The request is the same for private and public torrents: no
+    /// announce ever carries DHT or peer exchange parameters. Privacy is
+    /// enforced elsewhere; a private torrent gets no DHT bootstrap nodes and
+    /// its [`TrackerManager`] refuses extra trackers, and callers must keep
+    /// PEX disabled for it.
+    pub fn for_torrent(metainfo: &Metainfo, peer_id: [u8; 20], port: u16) -> Self {
+        Self::new_started(metainfo.info_hash, peer_id, port, metainfo.total_size())
+    }
+
     /// Create a new tracker request for starting a download
     pub fn new_started(info_hash: [u8; 20], peer_id: [u8; 20], port: u16, total_size: u64) -> Self {
-        Self {
-            info_hash,
-            peer_id,
-            port,
-            uploaded: 0,
-            downloaded: 0,
-            left: total_size,
-            compact: true,
-            event: Some(TrackerEvent::Started),
+        Self::builder(info_hash, peer_id, port, total_size)
+            .event(TrackerEvent::Started)
+            .build()
+    }
+
+    /// Start building a request with no event, compact peers and every
+    /// optional parameter unset
+    pub fn builder(
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+        port: u16,
+        left: u64,
+    ) -> TrackerRequestBuilder {
+        TrackerRequestBuilder {
+            request: Self {
+                info_hash,
+                peer_id,
+                port,
+                uploaded: 0,
+                downloaded: 0,
+                left,
+                compact: true,
+                event: None,
+                key: None,
+                supportcrypto: false,
+                requirecrypto: false,
+                omit_transfer_stats: false,
+                numwant: None,
+                no_peer_id: false,
+                tracker_id: None,
+                ipv4: None,
+                ipv6: None,
+            },
         }
     }
 }
 
+/// Chained construction of a [`TrackerRequest`]; see
+/// [`TrackerRequest::builder`]
+#[derive(Debug, Clone)]
+pub struct TrackerRequestBuilder {
+    request: TrackerRequest,
+}
+
+impl TrackerRequestBuilder {
+    /// Bytes uploaded and downloaded this session
+    pub fn transferred(mut self, uploaded: u64, downloaded: u64) -> Self {
+        self.request.uploaded = uploaded;
+        self.request.downloaded = downloaded;
+        self
+    }
+
+    /// Announce event
+    pub fn event(mut self, event: TrackerEvent) -> Self {
+        self.request.event = Some(event);
+        self
+    }
+
+    /// Stable `key` identifying this client across IP changes
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.request.key = Some(key.into());
+        self
+    }
+
+    /// Number of peers to ask for
+    pub fn numwant(mut self, numwant: u32) -> Self {
+        self.request.numwant = Some(numwant);
+        self
+    }
+
+    /// Ask for peer lists without peer IDs
+    pub fn no_peer_id(mut self) -> Self {
+        self.request.no_peer_id = true;
+        self
+    }
+
+    /// `tracker id` from the tracker's previous response
+    pub fn tracker_id(mut self, tracker_id: impl Into<String>) -> Self {
+        self.request.tracker_id = Some(tracker_id.into());
+        self
+    }
+
+    /// The finished request
+    pub fn build(self) -> TrackerRequest {
+        self.request
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,12 +943,564 @@ mod tests {
             192, 168, 1, 2, 0x1A, 0xE2, // 6882 = 0x1AE2
         ];
 
-        let peers = Tracker::parse_compact_peers(&data).unwrap();
+        let peers = Tracker::parse_compact_peers(&data, true).unwrap();
         assert_eq!(peers.len(), 2);
         assert_eq!(peers[0].port(), 6881);
         assert_eq!(peers[1].port(), 6882);
     }
 
+    #[test]
+    fn test_parse_truncated_compact_peers() {
+        // Two complete entries followed by one stray byte
+        let data = vec![
+            192, 168, 1, 1, 0x1A, 0xE1, //
+            192, 168, 1, 2, 0x1A, 0xE2, //
+            192,
+        ];
+
+        let peers = Tracker::parse_compact_peers(&data, false).unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[1].port(), 6882);
+
+        assert!(Tracker::parse_compact_peers(&data, true).is_err());
+    }
+
+    #[test]
+    fn test_peers6_merged() {
+        let tracker = Tracker::new(String::new());
+        let mut body = b"d8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe16:peers636:".to_vec();
+        body.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        body.extend_from_slice(&[0x1A, 0xE2]);
+        body.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        body.extend_from_slice(&[0x1A, 0xE3]);
+        body.push(b'e');
+
+        let response = tracker.parse_response(&body).unwrap();
+        let expected: Vec<SocketAddr> = vec![
+            "127.0.0.1:6881".parse().unwrap(),
+            "[2001:db8::1]:6882".parse().unwrap(),
+            "[::1]:6883".parse().unwrap(),
+        ];
+        assert_eq!(response.peers, expected);
+
+        let truncated = b"d8:intervali1800e5:peers0:6:peers65:12345e";
+        assert!(matches!(
+            tracker.parse_response(truncated),
+            Err(crate::Error::Tracker(_))
+        ));
+    }
+
+    #[test]
+    fn test_dict_peers_ipv6() {
+        let tracker = Tracker::new(String::new());
+        let body = b"d8:intervali1800e5:peersl\
+                     d2:ip11:2001:db8::14:porti6881ee\
+                     d2:ip8:[::ffff]4:porti6882ee\
+                     d2:ip8:10.0.0.14:porti6883ee\
+                     d2:ip8:10.0.0.24:porti70000eeee";
+
+        let response = tracker.parse_response(body).unwrap();
+        let expected: Vec<SocketAddr> = vec![
+            "[2001:db8::1]:6881".parse().unwrap(),
+            "[::ffff]:6882".parse().unwrap(),
+            "10.0.0.1:6883".parse().unwrap(),
+        ];
+        assert_eq!(response.peers, expected);
+    }
+
+    #[test]
+    fn test_dict_peers_with_peer_ids() {
+        let tracker = Tracker::new(String::new());
+        let body = b"d8:intervali1800e5:peersl\
+                     d2:ip8:10.0.0.17:peer id20:-TR2940-\x00\x01\xff\xfeabcdefgh4:porti6881ee\
+                     d2:ip8:10.0.0.27:peer id20:-qB4250-0123456789ab4:porti6882eeee";
+
+        let response = tracker.parse_response(body).unwrap();
+        let expected: Vec<SocketAddr> = vec![
+            "10.0.0.1:6881".parse().unwrap(),
+            "10.0.0.2:6882".parse().unwrap(),
+        ];
+        assert_eq!(response.peers, expected);
+    }
+
+    #[test]
+    fn test_dict_peers_hostnames_left_unresolved() {
+        let tracker = Tracker::new(String::new());
+        let body = b"d8:intervali1800e5:peersl\
+                     d2:ip9:127.0.0.14:porti6881ee\
+                     d2:ip13:[2001:db8::1]4:porti6882ee\
+                     d2:ip16:peer.example.org4:porti6883ee\
+                     d2:ip0:4:porti6884eeee";
+
+        let response = tracker.parse_response(body).unwrap();
+        let expected: Vec<SocketAddr> = vec![
+            "127.0.0.1:6881".parse().unwrap(),
+            "[2001:db8::1]:6882".parse().unwrap(),
+        ];
+        assert_eq!(response.peers, expected);
+        assert_eq!(
+            response.peer_hosts,
+            vec![("peer.example.org".to_string(), 6883)]
+        );
+
+        // Resolution is the caller's step; an IP literal needs no DNS
+        let mut response = response;
+        response.peer_hosts = vec![("127.0.0.2".to_string(), 6885)];
+        assert_eq!(
+            response.resolve_peer_hosts(),
+            vec!["127.0.0.2:6885".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_plaintext_error_body() {
+        let mock = mock::MockTracker::with_response(b"Unregistered torrent\n".to_vec());
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        match Tracker::new(mock.announce_url()).announce(&request) {
+            Err(crate::Error::Tracker(message)) => {
+                assert!(message.ends_with(": Unregistered torrent"), "{}", message)
+            }
+            other => panic!("Expected a tracker error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_announce_raw_returns_body() {
+        let body = b"d8:intervali900e5:peers6:\x7f\x00\x00\x01\x1a\xe1\
+                     7:privatei1ee";
+        let mock = mock::MockTracker::with_response(body.to_vec());
+
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+        let (response, raw) = Tracker::new(mock.announce_url())
+            .announce_raw(&request)
+            .unwrap();
+
+        assert_eq!(raw, body);
+        assert_eq!(response.interval, 900);
+        assert_eq!(response.peers, vec!["127.0.0.1:6881".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_announce_many_reuses_connection() {
+        let mock = mock::MockTracker::start();
+        let tracker = Tracker::new(mock.announce_url());
+        let requests: Vec<_> = (0..3u8)
+            .map(|i| TrackerRequest::new_started([i; 20], [2u8; 20], 6881, 1000))
+            .collect();
+
+        let results = tracker.announce_many(&requests);
+
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(mock.requests().len(), 3);
+        assert_eq!(mock.connections(), 1);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_announce_async() {
+        let mock = mock::MockTracker::start();
+        let tracker = Tracker::new(mock.announce_url());
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        let response = tracker.announce_async(&request).await.unwrap();
+        assert_eq!(response.interval, 1800);
+        let target = &mock.requests()[0];
+        assert!(target.starts_with("/announce?info_hash="), "{}", target);
+
+        let tracker = tracker.with_retry(3, Duration::from_millis(1));
+        mock.fail_next(2, 502);
+        assert!(tracker.announce_async(&request).await.is_ok());
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_announce_async_defers_hostnames() {
+        let mock = mock::MockTracker::with_response(
+            b"d8:intervali1800e5:peersl\
+              d2:ip8:10.0.0.14:porti6881ee\
+              d2:ip16:peer.example.org4:porti6882eeee"
+                .to_vec(),
+        );
+        let tracker = Tracker::new(mock.announce_url());
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        let mut response = tracker.announce_async(&request).await.unwrap();
+        assert_eq!(response.peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+        assert_eq!(
+            response.peer_hosts,
+            vec![("peer.example.org".to_string(), 6882)]
+        );
+
+        response.peer_hosts = vec![("127.0.0.2".to_string(), 6883)];
+        assert_eq!(
+            response.resolve_peer_hosts_async().await,
+            vec!["127.0.0.2:6883".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_announce_logs_sizes() {
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mock = mock::MockTracker::start();
+        let tracker = Tracker::new(mock.announce_url());
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let (_, stats) = tracing::subscriber::with_default(subscriber, || {
+            tracker.announce_with_stats(&request).unwrap()
+        });
+
+        let url = tracker.build_url(&request).unwrap();
+        assert_eq!(
+            stats,
+            AnnounceStats {
+                request_url_len: url.len(),
+                response_body_len: b"d8:intervali1800e5:peers0:e".len(),
+            }
+        );
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains(&format!("request_url_len={}", url.len())));
+        assert!(logs.contains("response_body_len=27"));
+    }
+
+    #[test]
+    fn test_crypto_params() {
+        let tracker = Tracker::new("http://tracker.test/announce".to_string());
+        let mut request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        let url = tracker.build_url(&request).unwrap();
+        assert!(!url.contains("supportcrypto"));
+        assert!(!url.contains("requirecrypto"));
+
+        request.supportcrypto = true;
+        request.requirecrypto = true;
+        let url = tracker.build_url(&request).unwrap();
+        assert!(url.contains("&supportcrypto=1"));
+        assert!(url.contains("&requirecrypto=1"));
+    }
+
+    #[test]
+    fn test_state_key() {
+        let a = Tracker::new("http://tracker.test/announce?passkey=a/b".to_string());
+        let b = Tracker::new("udp://tracker.test:6969".to_string());
+
+        assert_eq!(
+            a.state_key(),
+            Tracker::new("http://tracker.test/announce?passkey=a/b".to_string()).state_key()
+        );
+        assert_ne!(a.state_key(), b.state_key());
+        assert!(a.state_key().chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_query_parameter_order() {
+        let tracker = Tracker::new("http://tracker.test/announce?passkey=abc".to_string());
+        let mut request = TrackerRequest::new_started([0xAB; 20], [b'-'; 20], 6881, 1000);
+        request.key = Some("1A2B3C4D".to_string());
+        request.supportcrypto = true;
+
+        let url = tracker.build_url(&request).unwrap();
+        let (_, query) = url.split_once('?').unwrap();
+        let names: Vec<&str> = query
+            .split('&')
+            .map(|pair| pair.split('=').next().unwrap())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "passkey",
+                "info_hash",
+                "peer_id",
+                "port",
+                "uploaded",
+                "downloaded",
+                "left",
+                "compact",
+                "event",
+                "key",
+                "supportcrypto"
+            ]
+        );
+        // Binary values are escaped exactly once
+        assert!(query.contains(&format!("info_hash={}", "%ab".repeat(20))));
+        assert!(!query.contains("%25"));
+    }
+
+    #[test]
+    fn test_passkey_preserved() {
+        let mock = mock::MockTracker::start();
+        let announce_url = format!("{}?passkey=a%2Bb&uid=7", mock.announce_url());
+        let request = TrackerRequest::new_started([0xAB; 20], [b'-'; 20], 6881, 1000);
+
+        Tracker::new(announce_url).announce(&request).unwrap();
+
+        let target = &mock.requests()[0];
+        let (path, query) = target.split_once('?').unwrap();
+        assert_eq!(path, "/announce");
+        // Existing parameters come first, exactly as written
+        assert!(query.starts_with("passkey=a%2Bb&uid=7&"), "{}", query);
+        assert!(query.contains(&format!("&info_hash={}&", "%ab".repeat(20))));
+    }
+
+    #[test]
+    fn test_for_torrent() {
+        let torrent = b"d8:announce9:localhost4:infod6:lengthi1000e4:name4:test\
+                        12:piece lengthi512e6:pieces40:\
+                        12345678901234567890123456789012345678907:privatei1eee";
+        let metainfo = Metainfo::from_bytes(torrent).unwrap();
+        assert!(metainfo.info.private);
+
+        let request = TrackerRequest::for_torrent(&metainfo, [2u8; 20], 6881);
+        assert_eq!(request.info_hash, metainfo.info_hash);
+        assert_eq!(request.left, 1000);
+        assert!(matches!(request.event, Some(TrackerEvent::Started)));
+    }
+
+    #[test]
+    fn test_swarm_counts() {
+        let peers: Vec<SocketAddr> = vec!["10.0.0.1:6881".parse().unwrap()];
+        let response = TrackerResponse {
+            interval: 1800,
+            min_interval: None,
+            peers: peers.clone(),
+            peer_hosts: Vec::new(),
+            seeders: Some(12),
+            leechers: Some(30),
+            warning: None,
+            tracker_id: None,
+        };
+        assert_eq!(response.seeder_count(), 12);
+        assert_eq!(response.leecher_count(), 30);
+        assert_eq!(response.swarm_size(), 42);
+
+        let response = TrackerResponse {
+            interval: 1800,
+            min_interval: None,
+            peers,
+            peer_hosts: Vec::new(),
+            seeders: None,
+            leechers: None,
+            warning: None,
+            tracker_id: None,
+        };
+        assert_eq!(response.seeder_count(), 0);
+        assert_eq!(response.swarm_size(), 1);
+    }
+
+    #[test]
+    fn test_query_pairs_round_trip() {
+        let mut info_hash = [0u8; 20];
+        info_hash
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, b)| *b = (i * 13) as u8);
+        let mut request =
+            TrackerRequest::new_started(info_hash, *b"-RS0100-\x00%&=+ \xff\xfeabcd", 6881, 42);
+        request.uploaded = 7;
+        request.downloaded = 9;
+        request.key = Some("A1 B2".to_string());
+        request.requirecrypto = true;
+        request.numwant = Some(50);
+        request.tracker_id = Some("id/1".to_string());
+        request.ipv4 = Some(Ipv4Addr::new(198, 51, 100, 7));
+        request.ipv6 = Some("[2001:db8::1]:6882".parse().unwrap());
+
+        let pairs = request.to_query_pairs();
+        let restored = TrackerRequest::from_query_pairs(&pairs).unwrap();
+        assert_eq!(format!("{:?}", restored), format!("{:?}", request));
+
+        // Omitted statistics aren't sent, so they come back as zero
+        request.omit_transfer_stats = true;
+        request.uploaded = 0;
+        request.downloaded = 0;
+        request.event = None;
+        let restored = TrackerRequest::from_query_pairs(&request.to_query_pairs()).unwrap();
+        assert_eq!(format!("{:?}", restored), format!("{:?}", request));
+
+        assert!(TrackerRequest::from_query_pairs(&pairs[1..]).is_err());
+    }
+
+    #[test]
+    fn test_builder_optional_parameters() {
+        let tracker = Tracker::new("http://t.example/announce".to_string());
+        let plain = TrackerRequest::builder([1u8; 20], [2u8; 20], 6881, 1000).build();
+        let url = tracker.build_url(&plain).unwrap();
+        for name in ["numwant=", "key=", "no_peer_id=", "event="] {
+            assert!(!url.contains(name), "{}", url);
+        }
+
+        let request = TrackerRequest::builder([1u8; 20], [2u8; 20], 6881, 1000)
+            .transferred(5, 7)
+            .event(TrackerEvent::Completed)
+            .key("K1")
+            .numwant(30)
+            .no_peer_id()
+            .build();
+        let url = tracker.build_url(&request).unwrap();
+        assert!(url.contains("&uploaded=5&downloaded=7&"), "{}", url);
+        assert!(
+            url.contains("&compact=1&no_peer_id=1&event=completed&key=K1&numwant=30"),
+            "{}",
+            url
+        );
+
+        let restored = TrackerRequest::from_query_pairs(&request.to_query_pairs()).unwrap();
+        assert!(restored.no_peer_id);
+    }
+
+    #[test]
+    fn test_ipv6_parameter() {
+        let mut request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+        let url = Tracker::new("http://t.example/announce".to_string())
+            .build_url(&request)
+            .unwrap();
+        assert!(!url.contains("ipv6="), "{}", url);
+
+        request.ipv6 = Some("[2001:db8::1]:6882".parse().unwrap());
+        let url = Tracker::new("http://t.example/announce".to_string())
+            .build_url(&request)
+            .unwrap();
+        assert!(
+            url.contains("&ipv6=%5B2001%3Adb8%3A%3A1%5D%3A6882"),
+            "{}",
+            url
+        );
+
+        // A bare address announced by another client implies `port`
+        let mut pairs = request.to_query_pairs();
+        pairs.retain(|(name, _)| name != "ipv6");
+        pairs.push(("ipv6".to_string(), "2001:db8::2".to_string()));
+        let restored = TrackerRequest::from_query_pairs(&pairs).unwrap();
+        assert_eq!(restored.ipv6, Some("[2001:db8::2]:6881".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_next_announce_delay_bounds() {
+        let mut response = TrackerResponse {
+            interval: 1000,
+            min_interval: None,
+            peers: Vec::new(),
+            peer_hosts: Vec::new(),
+            seeders: None,
+            leechers: None,
+            warning: None,
+            tracker_id: None,
+        };
+        for _ in 0..1000 {
+            let delay = response.next_announce_delay();
+            assert!(delay >= Duration::from_secs(900) && delay <= Duration::from_secs(1100));
+        }
+
+        response.min_interval = Some(1050);
+        for _ in 0..1000 {
+            let delay = response.next_announce_delay();
+            assert!(delay >= Duration::from_secs(1050) && delay <= Duration::from_secs(1100));
+        }
+    }
+
+    #[test]
+    fn test_min_interval_parsed() {
+        let tracker = Tracker::new(String::new());
+        let response = tracker
+            .parse_response(b"d8:intervali1800e12:min intervali900e5:peers0:e")
+            .unwrap();
+        assert_eq!(response.min_interval, Some(900));
+    }
+
+    #[test]
+    fn test_warning_message_keeps_peers() {
+        let tracker = Tracker::new(String::new());
+        let response = tracker
+            .parse_response(
+                b"d8:intervali1800e15:warning message17:Client is too old\
+                  5:peers6:\x7f\x00\x00\x01\x1a\xe1e",
+            )
+            .unwrap();
+        assert_eq!(response.warning.as_deref(), Some("Client is too old"));
+        assert_eq!(response.peers, vec!["127.0.0.1:6881".parse().unwrap()]);
+
+        let response = tracker
+            .parse_response(b"d8:intervali1800e5:peers0:e")
+            .unwrap();
+        assert_eq!(response.warning, None);
+    }
+
+    #[test]
+    fn test_failure_reason() {
+        let tracker = Tracker::new(String::new());
+        match tracker.parse_response(b"d14:failure reason22:torrent not registerede") {
+            Err(crate::Error::Tracker(reason)) => assert_eq!(reason, "torrent not registered"),
+            other => panic!("Expected a tracker error, got {:?}", other),
+        }
+
+        // A failure wins even when the tracker also sends an interval
+        let body = b"d14:failure reason6:banned8:intervali1800e5:peers0:e";
+        assert!(matches!(
+            tracker.parse_response(body),
+            Err(crate::Error::Tracker(reason)) if reason == "banned"
+        ));
+    }
+
+    #[test]
+    fn test_string_encoded_integers() {
+        let tracker = Tracker::new(String::new());
+        let body = b"d8:complete1:58:interval4:180012:min interval3:9005:peers0:e";
+        let response = tracker.parse_response(body).unwrap();
+        assert_eq!(response.interval, 1800);
+        assert_eq!(response.min_interval, Some(900));
+        assert_eq!(response.seeders, Some(5));
+        assert_eq!(response.leechers, None);
+
+        let body = b"d8:interval4:soon5:peers0:e";
+        assert!(matches!(
+            tracker.parse_response(body),
+            Err(crate::Error::Tracker(_))
+        ));
+    }
+
+    #[test]
+    fn test_double_bencoded_response() {
+        let tracker = Tracker::new(String::new());
+        let inner = b"d8:intervali900e5:peers6:\x7f\x00\x00\x01\x1a\xe1e";
+        let mut body = format!("{}:", inner.len()).into_bytes();
+        body.extend_from_slice(inner);
+
+        let response = tracker.parse_response(&body).unwrap();
+        assert_eq!(response.interval, 900);
+        assert_eq!(response.peers, vec!["127.0.0.1:6881".parse().unwrap()]);
+
+        // Only one layer is unwrapped
+        let mut twice = format!("{}:", body.len()).into_bytes();
+        twice.extend_from_slice(&body);
+        assert!(tracker.parse_response(&twice).is_err());
+    }
+
     #[test]
     fn test_url_encode_bytes() {
         let bytes = [0x12, 0x34, 0xAB, 0xCD];
@@ -0,0 +1,175 @@
+//! Retrying HTTP announces
+//!
+//! Trackers are often briefly unreachable or overloaded. Failures that may
+//! go away on their own (timeouts, refused or reset connections, 5xx
+//! statuses) are retried after an exponentially growing delay; anything the
+//! tracker answered deliberately, such as a 4xx or a `failure reason`, is
+//! returned at once.
+
+use std::io::ErrorKind;
+use std::time::Duration;
+
+/// How often and how patiently to retry a failed HTTP announce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total tries, including the first; 1 means no retries
+    pub attempts: u32,
+    /// Wait before the first retry; doubles after each one
+    pub base_delay: Duration,
+    /// Longest wait between two tries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// A single attempt, no retries
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry` (counting from 0)
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    /// Run `attempt` until it succeeds, fails for good or runs out of tries
+    pub(super) fn run<T>(&self, mut attempt: impl FnMut() -> crate::Result<T>) -> crate::Result<T> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Err(e) => match self.next_delay(retry, &e) {
+                    Some(delay) => std::thread::sleep(delay),
+                    None => return Err(e),
+                },
+                Ok(value) => return Ok(value),
+            }
+            retry += 1;
+        }
+    }
+
+    /// Like [`RetryPolicy::run`], but sleeping without blocking the runtime
+    #[cfg(feature = "async")]
+    pub(super) async fn run_async<T, F>(&self, mut attempt: impl FnMut() -> F) -> crate::Result<T>
+    where
+        F: std::future::Future<Output = crate::Result<T>>,
+    {
+        let mut retry = 0;
+        loop {
+            match attempt().await {
+                Err(e) => match self.next_delay(retry, &e) {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => return Err(e),
+                },
+                Ok(value) => return Ok(value),
+            }
+            retry += 1;
+        }
+    }
+
+    /// Wait before retry number `retry` after `error`, or `None` to give up
+    fn next_delay(&self, retry: u32, error: &crate::Error) -> Option<Duration> {
+        if retry + 1 >= self.attempts || !is_retryable(error) {
+            return None;
+        }
+        let delay = self.delay(retry);
+        tracing::warn!("Announce failed ({}), retrying in {:?}", error, delay);
+        Some(delay)
+    }
+}
+
+/// Whether an announce failing with `error` is worth trying again
+///
+/// True for timeouts, connection failures and resets, and 5xx statuses.
+/// False for everything else, including 4xx statuses and tracker errors.
+pub fn is_retryable(error: &crate::Error) -> bool {
+    match error {
+        crate::Error::Http(e) => match e.status() {
+            Some(status) => status.is_server_error(),
+            None => e.is_timeout() || e.is_connect() || e.is_body(),
+        },
+        crate::Error::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::TimedOut
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::Interrupted
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::mock::MockTracker;
+    use crate::tracker::{Tracker, TrackerRequest};
+
+    fn request() -> TrackerRequest {
+        TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000)
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_max() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(4), Duration::from_secs(1));
+        assert_eq!(policy.delay(40), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let mock = MockTracker::start();
+        let tracker = Tracker::new(mock.announce_url());
+
+        mock.fail_next(1, 503);
+        let server_error = tracker.announce(&request()).unwrap_err();
+        assert!(is_retryable(&server_error), "{}", server_error);
+
+        mock.fail_next(1, 404);
+        let client_error = tracker.announce(&request()).unwrap_err();
+        assert!(!is_retryable(&client_error), "{}", client_error);
+
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/announce", closed.local_addr().unwrap());
+        drop(closed);
+        let refused = Tracker::new(url).announce(&request()).unwrap_err();
+        assert!(is_retryable(&refused), "{}", refused);
+
+        let reset = std::io::Error::from(ErrorKind::ConnectionReset);
+        assert!(is_retryable(&crate::Error::Io(reset)));
+        assert!(!is_retryable(&crate::Error::Tracker("banned".to_string())));
+    }
+
+    #[test]
+    fn test_retries_transient_failures() {
+        let mock = MockTracker::start();
+        let tracker = Tracker::new(mock.announce_url()).with_retry(3, Duration::from_millis(1));
+
+        mock.fail_next(2, 502);
+        assert!(tracker.announce(&request()).is_ok());
+        assert_eq!(mock.requests().len(), 3);
+
+        mock.fail_next(3, 502);
+        assert!(tracker.announce(&request()).is_err());
+        assert_eq!(mock.requests().len(), 6);
+
+        // A 4xx is the tracker's answer, not a glitch
+        mock.fail_next(1, 403);
+        assert!(tracker.announce(&request()).is_err());
+        assert_eq!(mock.requests().len(), 7);
+    }
+}
@@ -0,0 +1,187 @@
+//! Tracker scrape requests
+//!
+//! A scrape asks an HTTP tracker for swarm counts without announcing. By
+//! convention the scrape URL is the announce URL with its final `announce`
+//! path segment replaced by `scrape`; trackers whose URL doesn't follow it
+//! don't support scraping.
+
+use super::Tracker;
+use serde::Deserialize;
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use url::Url;
+
+/// Swarm counts for one torrent, as reported by a scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrapeStats {
+    /// Torrent the counts belong to
+    pub info_hash: [u8; 20],
+    /// Peers with the complete torrent (seeders)
+    pub complete: u32,
+    /// Completed downloads the tracker has recorded
+    pub downloaded: u32,
+    /// Peers still downloading (leechers)
+    pub incomplete: u32,
+}
+
+#[derive(Deserialize)]
+struct BencodeScrapeResponse {
+    #[serde(default)]
+    files: BTreeMap<ByteBuf, BencodeScrapeFile>,
+}
+
+#[derive(Deserialize)]
+struct BencodeScrapeFile {
+    #[serde(default)]
+    complete: i64,
+    #[serde(default)]
+    downloaded: i64,
+    #[serde(default)]
+    incomplete: i64,
+}
+
+impl Tracker {
+    /// Fetch swarm counts for `info_hashes` in one request
+    ///
+    /// Results follow the order of `info_hashes`. Torrents the tracker
+    /// doesn't report on are left out, so the result may be shorter than
+    /// the input.
+    pub fn scrape(&self, info_hashes: &[[u8; 20]]) -> crate::Result<Vec<ScrapeStats>> {
+        let url = self.scrape_url(info_hashes)?;
+
+        tracing::debug!("Scraping tracker: {}", url);
+
+        let body = reqwest::blocking::get(&url)?.bytes()?;
+        super::check_failure(&body)?;
+        let response: BencodeScrapeResponse =
+            serde_bencode::from_bytes(&body).map_err(|e| crate::Error::Tracker(e.to_string()))?;
+
+        let stats: Vec<ScrapeStats> = info_hashes
+            .iter()
+            .filter_map(|info_hash| {
+                let file = response.files.get(&ByteBuf::from(info_hash.to_vec()))?;
+                // Negative or oversized counts are nonsense; report none
+                let count = |n: i64| u32::try_from(n).unwrap_or(0);
+                Some(ScrapeStats {
+                    info_hash: *info_hash,
+                    complete: count(file.complete),
+                    downloaded: count(file.downloaded),
+                    incomplete: count(file.incomplete),
+                })
+            })
+            .collect();
+
+        if stats.len() < info_hashes.len() {
+            tracing::debug!(
+                "Tracker reported {} of {} scraped torrents",
+                stats.len(),
+                info_hashes.len()
+            );
+        }
+
+        Ok(stats)
+    }
+
+    /// Scrape URL with one `info_hash` parameter per torrent
+    fn scrape_url(&self, info_hashes: &[[u8; 20]]) -> crate::Result<String> {
+        let unsupported = || {
+            crate::Error::Tracker(format!(
+                "Tracker does not support scrape: {}",
+                self.announce_url
+            ))
+        };
+
+        if self.is_udp() {
+            return Err(unsupported());
+        }
+        let mut url = Url::parse(&self.announce_url)?;
+
+        // Only the last segment may change, and it must start with
+        // `announce` (so `announce.php` becomes `scrape.php`)
+        let path = url.path().to_string();
+        let (dir, last) = path.rsplit_once('/').ok_or_else(unsupported)?;
+        let rest = last.strip_prefix("announce").ok_or_else(unsupported)?;
+        url.set_path(&format!("{}/scrape{}", dir, rest));
+
+        let scrape_query = info_hashes
+            .iter()
+            .map(|info_hash| format!("info_hash={}", Tracker::url_encode_bytes(info_hash)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let query = match url.query() {
+            Some(existing) if !existing.is_empty() => format!("{}&{}", existing, scrape_query),
+            _ => scrape_query,
+        };
+        url.set_query(Some(&query));
+
+        Ok(url.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::mock::MockTracker;
+
+    #[test]
+    fn test_scrape_url() {
+        let hash = [0xABu8; 20];
+        let encoded = "%ab".repeat(20);
+
+        let tracker = Tracker::new("http://t.example/a/announce.php?passkey=x".to_string());
+        assert_eq!(
+            tracker.scrape_url(&[hash]).unwrap(),
+            format!(
+                "http://t.example/a/scrape.php?passkey=x&info_hash={}",
+                encoded
+            )
+        );
+
+        for url in [
+            "http://t.example/a",
+            "http://t.example/announce/x",
+            "udp://t.example:80/announce",
+        ] {
+            assert!(Tracker::new(url.to_string()).scrape_url(&[hash]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_scrape_partial_response() {
+        let known = [1u8; 20];
+        let unknown = [2u8; 20];
+        let bogus = [3u8; 20];
+        let mut body = b"d5:filesd20:".to_vec();
+        body.extend_from_slice(&known);
+        body.extend_from_slice(b"d8:completei5e10:downloadedi50e10:incompletei10ee20:");
+        body.extend_from_slice(&bogus);
+        body.extend_from_slice(b"d8:completei-1e10:downloadedi4294967296e10:incompletei7eeee");
+        let mock = MockTracker::with_response(body);
+
+        let stats = Tracker::new(mock.announce_url())
+            .scrape(&[unknown, known, bogus])
+            .unwrap();
+        assert_eq!(
+            stats,
+            vec![
+                ScrapeStats {
+                    info_hash: known,
+                    complete: 5,
+                    downloaded: 50,
+                    incomplete: 10,
+                },
+                // Out-of-range counts don't wrap around
+                ScrapeStats {
+                    info_hash: bogus,
+                    complete: 0,
+                    downloaded: 0,
+                    incomplete: 7,
+                },
+            ]
+        );
+
+        let target = &mock.requests()[0];
+        assert!(target.starts_with("/scrape?"), "{}", target);
+        assert_eq!(target.matches("info_hash=").count(), 3);
+    }
+}
@@ -0,0 +1,429 @@
+//! Announce lifecycle for a single torrent on a single tracker
+//!
+//! A session remembers the per-client values that must stay the same across
+//! announces (such as the `key`) and tracks transfer statistics between them.
+
+use super::{Tracker, TrackerEvent, TrackerRequest, TrackerResponse};
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Most peers [`TrackerSession::numwant_for`] asks for in one announce
+pub const MAX_NUMWANT: u32 = 200;
+
+/// Wait after the first failed announce; doubles with each further failure
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(15);
+
+/// Longest backoff between failed announces, before jitter
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// Callback told when the next announce is due
+type Scheduler = Box<dyn FnMut(Instant) + Send>;
+
+/// Announce state for one torrent on one tracker
+pub struct TrackerSession {
+    tracker: Tracker,
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    port: u16,
+    key: String,
+    uploaded: u64,
+    downloaded: u64,
+    left: u64,
+    scheduler: Option<Scheduler>,
+    minimal_stop: bool,
+    peer_target: Option<usize>,
+    connected_peers: usize,
+    tracker_id: Option<String>,
+    min_interval: Option<u32>,
+}
+
+impl TrackerSession {
+    /// Create a session with a freshly generated `key`
+    pub fn new(
+        tracker: Tracker,
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+        port: u16,
+        left: u64,
+    ) -> Self {
+        Self {
+            tracker,
+            info_hash,
+            peer_id,
+            port,
+            key: generate_key(),
+            uploaded: 0,
+            downloaded: 0,
+            left,
+            scheduler: None,
+            minimal_stop: false,
+            peer_target: None,
+            connected_peers: 0,
+            tracker_id: None,
+            min_interval: None,
+        }
+    }
+
+    /// Register a callback invoked after each successful announce with the
+    /// instant the next one is due, so an event loop can set a timer
+    pub fn with_scheduler(mut self, scheduler: impl FnMut(Instant) + Send + 'static) -> Self {
+        self.scheduler = Some(Box::new(scheduler));
+        self
+    }
+
+    /// Reuse a `key` persisted from an earlier session
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Send `stopped` announces without `uploaded`/`downloaded`, for
+    /// trackers that reject the full parameter set on stop
+    pub fn with_minimal_stop(mut self, minimal_stop: bool) -> Self {
+        self.minimal_stop = minimal_stop;
+        self
+    }
+
+    /// Ask periodic announces for only as many peers as it takes to reach
+    /// `target` connections; see [`TrackerSession::set_connected_peers`]
+    pub fn with_peer_target(mut self, target: usize) -> Self {
+        self.peer_target = Some(target);
+        self
+    }
+
+    /// Record how many peers are connected, for sizing the next `numwant`
+    pub fn set_connected_peers(&mut self, count: usize) {
+        self.connected_peers = count;
+    }
+
+    /// How many more peers to ask for to reach `target` from `current_peers`
+    ///
+    /// Zero once the target is met, and never more than [`MAX_NUMWANT`].
+    pub fn numwant_for(current_peers: usize, target: usize) -> u32 {
+        let missing = target.saturating_sub(current_peers);
+        u32::try_from(missing).unwrap_or(u32::MAX).min(MAX_NUMWANT)
+    }
+
+    /// How long to wait before retrying after `attempt` consecutive failed
+    /// announces (1 for the first failure)
+    ///
+    /// Starts at 15 seconds and doubles with each failure up to 30 minutes,
+    /// plus up to 10% random jitter so clients that failed together don't
+    /// retry together. Never shorter than the last `min interval` the
+    /// tracker sent.
+    pub fn retry_schedule(&self, attempt: u32) -> Duration {
+        let backoff = RETRY_BASE_DELAY
+            .checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .unwrap_or(RETRY_MAX_DELAY)
+            .min(RETRY_MAX_DELAY);
+        let jitter = backoff.mul_f64(rand::thread_rng().gen_range(0.0..0.1));
+        let floor = Duration::from_secs(self.min_interval.unwrap_or(0).into());
+        (backoff + jitter).max(floor)
+    }
+
+    /// The `key` sent with every announce, for persisting across restarts
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Announce that the download has started
+    pub fn start(&mut self) -> crate::Result<TrackerResponse> {
+        self.announce(Some(TrackerEvent::Started))
+    }
+
+    /// Periodic announce with updated transfer statistics
+    pub fn update(
+        &mut self,
+        uploaded: u64,
+        downloaded: u64,
+        left: u64,
+    ) -> crate::Result<TrackerResponse> {
+        self.uploaded = uploaded;
+        self.downloaded = downloaded;
+        self.left = left;
+        self.announce(None)
+    }
+
+    /// Announce that the download has completed
+    pub fn complete(&mut self) -> crate::Result<TrackerResponse> {
+        self.left = 0;
+        self.announce(Some(TrackerEvent::Completed))
+    }
+
+    /// Announce that we are leaving the swarm
+    pub fn stop(&mut self) -> crate::Result<TrackerResponse> {
+        self.announce(Some(TrackerEvent::Stopped))
+    }
+
+    fn announce(&mut self, event: Option<TrackerEvent>) -> crate::Result<TrackerResponse> {
+        // Only periodic announces are sized to the peer target
+        let numwant = match event {
+            None => self
+                .peer_target
+                .map(|target| Self::numwant_for(self.connected_peers, target)),
+            Some(_) => None,
+        };
+        let omit_transfer_stats = self.minimal_stop && matches!(event, Some(TrackerEvent::Stopped));
+        let request = TrackerRequest {
+            info_hash: self.info_hash,
+            peer_id: self.peer_id,
+            port: self.port,
+            uploaded: self.uploaded,
+            downloaded: self.downloaded,
+            left: self.left,
+            compact: true,
+            event,
+            key: Some(self.key.clone()),
+            supportcrypto: false,
+            requirecrypto: false,
+            omit_transfer_stats,
+            numwant,
+            no_peer_id: false,
+            tracker_id: self.tracker_id.clone(),
+            ipv4: None,
+            ipv6: None,
+        };
+        let response = self.tracker.announce(&request)?;
+
+        // Trackers may send the id only once, so keep it until replaced
+        if response.tracker_id.is_some() {
+            self.tracker_id = response.tracker_id.clone();
+        }
+        if response.min_interval.is_some() {
+            self.min_interval = response.min_interval;
+        }
+
+        if let Some(scheduler) = &mut self.scheduler {
+            scheduler(Instant::now() + Duration::from_secs(response.interval.into()));
+        }
+
+        Ok(response)
+    }
+}
+
+/// Random 8-character hex key, as sent by mainline clients
+fn generate_key() -> String {
+    format!("{:08X}", rand::thread_rng().r#gen::<u32>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::mock::MockTracker;
+
+    fn key_param(target: &str) -> Option<&str> {
+        target
+            .split(['?', '&'])
+            .find_map(|pair| pair.strip_prefix("key="))
+    }
+
+    #[test]
+    fn test_key_is_stable_across_announces() {
+        let mock = MockTracker::start();
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        );
+
+        session.start().unwrap();
+        session.update(10, 20, 980).unwrap();
+        session.stop().unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            assert_eq!(key_param(request), Some(session.key()));
+        }
+    }
+
+    #[test]
+    fn test_restored_key_is_sent() {
+        let mock = MockTracker::start();
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        )
+        .with_key("CAFEBABE");
+
+        session.start().unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(key_param(&requests[0]), Some("CAFEBABE"));
+    }
+
+    #[test]
+    fn test_scheduler_receives_next_announce_instant() {
+        use std::sync::{Arc, Mutex};
+
+        let mock = MockTracker::start();
+        let scheduled = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&scheduled);
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        )
+        .with_scheduler(move |at| sink.lock().unwrap().push(at));
+
+        let before = Instant::now();
+        session.start().unwrap();
+        session.update(0, 0, 1000).unwrap();
+        let after = Instant::now();
+
+        // The mock tracker answers with a 1800 second interval
+        let interval = Duration::from_secs(1800);
+        let scheduled = scheduled.lock().unwrap();
+        assert_eq!(scheduled.len(), 2);
+        for at in scheduled.iter() {
+            assert!(*at >= before + interval && *at <= after + interval);
+        }
+    }
+
+    #[test]
+    fn test_minimal_stop_url_shape() {
+        let mock = MockTracker::start();
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        )
+        .with_minimal_stop(true);
+
+        session.update(10, 20, 980).unwrap();
+        session.stop().unwrap();
+
+        let requests = mock.requests();
+        let params = |target: &str| -> Vec<String> {
+            let (_, query) = target.split_once('?').unwrap();
+            query
+                .split('&')
+                .map(|pair| pair.split('=').next().unwrap().to_string())
+                .collect()
+        };
+
+        // Regular announces still report transfer statistics
+        assert!(params(&requests[0]).contains(&"uploaded".to_string()));
+        assert_eq!(
+            params(&requests[1]),
+            vec![
+                "info_hash",
+                "peer_id",
+                "port",
+                "left",
+                "compact",
+                "event",
+                "key"
+            ]
+        );
+        assert!(requests[1].contains("event=stopped"));
+    }
+
+    #[test]
+    fn test_numwant_for() {
+        // Satiated
+        assert_eq!(TrackerSession::numwant_for(50, 50), 0);
+        assert_eq!(TrackerSession::numwant_for(80, 50), 0);
+        // Starved
+        assert_eq!(TrackerSession::numwant_for(10, 50), 40);
+        assert_eq!(TrackerSession::numwant_for(0, 1000), MAX_NUMWANT);
+    }
+
+    #[test]
+    fn test_update_sends_numwant() {
+        let mock = MockTracker::start();
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        )
+        .with_peer_target(50);
+
+        session.start().unwrap();
+        session.set_connected_peers(35);
+        session.update(0, 0, 1000).unwrap();
+
+        let requests = mock.requests();
+        assert!(!requests[0].contains("numwant="));
+        assert!(requests[1].contains("&numwant=15"), "{}", requests[1]);
+    }
+
+    #[test]
+    fn test_tracker_id_is_echoed() {
+        let mock =
+            MockTracker::with_response(b"d8:intervali1800e5:peers0:10:tracker id4:T-42e".to_vec());
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        );
+
+        assert_eq!(session.start().unwrap().tracker_id.as_deref(), Some("T-42"));
+        // Later responses without an id keep the earlier one
+        mock.set_response(b"d8:intervali1800e5:peers0:e".to_vec());
+        session.update(0, 0, 1000).unwrap();
+        session.update(0, 0, 1000).unwrap();
+
+        let requests = mock.requests();
+        assert!(!requests[0].contains("trackerid="));
+        assert!(requests[1].contains("&trackerid=T%2D42"), "{}", requests[1]);
+        assert!(requests[2].contains("&trackerid=T%2D42"), "{}", requests[2]);
+    }
+
+    #[test]
+    fn test_retry_schedule() {
+        let session = TrackerSession::new(Tracker::new(String::new()), [0; 20], [0; 20], 0, 0);
+        let within_jitter =
+            |delay: Duration, base: Duration| delay >= base && delay <= base.mul_f64(1.1);
+
+        // First failure
+        assert!(within_jitter(
+            session.retry_schedule(1),
+            Duration::from_secs(15)
+        ));
+        // Repeated failures double, up to the cap
+        assert!(within_jitter(
+            session.retry_schedule(3),
+            Duration::from_secs(60)
+        ));
+        assert!(within_jitter(session.retry_schedule(100), RETRY_MAX_DELAY));
+    }
+
+    #[test]
+    fn test_retry_schedule_respects_min_interval() {
+        let mock =
+            MockTracker::with_response(b"d8:intervali1800e12:min intervali600e5:peers0:e".to_vec());
+        let mut session = TrackerSession::new(
+            Tracker::new(mock.announce_url()),
+            [1u8; 20],
+            [2u8; 20],
+            6881,
+            1000,
+        );
+        session.start().unwrap();
+
+        assert_eq!(session.retry_schedule(1), Duration::from_secs(600));
+        assert!(session.retry_schedule(8) > Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_generated_keys_differ() {
+        let a = TrackerSession::new(Tracker::new(String::new()), [0; 20], [0; 20], 0, 0);
+        let b = TrackerSession::new(Tracker::new(String::new()), [0; 20], [0; 20], 0, 0);
+        assert_eq!(a.key().len(), 8);
+        assert_ne!(a.key(), b.key());
+    }
+}
@@ -0,0 +1,494 @@
+//! UDP tracker protocol (BEP 15)
+//!
+//! An announce is two round trips: a connect request to obtain a connection
+//! id, then the announce itself. UDP packets get lost, so each request is
+//! retransmitted after `base * 2^n` of silence, `n` counting retransmissions
+//! across the whole exchange and capped at 8. A connection id is only good
+//! for a minute, so a new one is fetched when a slow exchange outlives it.
+
+use super::{AnnounceStats, Tracker, TrackerEvent, TrackerRequest, TrackerResponse};
+use rand::Rng;
+use sha1::{Digest, Sha1};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Magic constant identifying a connect request
+const PROTOCOL_ID: u64 = 0x41727101980;
+
+const ACTION_CONNECT: u32 = 0;
+const ACTION_ANNOUNCE: u32 = 1;
+const ACTION_ERROR: u32 = 3;
+
+/// Largest `n` in the `base * 2^n` retransmission wait, as in BEP 15
+const MAX_BACKOFF_EXPONENT: u32 = 8;
+
+/// How long a tracker accepts a connection id after handing it out
+const CONNECTION_ID_TTL: Duration = Duration::from_secs(60);
+
+/// Retransmission schedule for UDP requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdpTimeouts {
+    /// Wait before the first retransmission; doubles after each one, up to
+    /// 256 times this
+    pub base: Duration,
+    /// Retransmissions before giving up
+    pub max_retries: u32,
+}
+
+impl Default for UdpTimeouts {
+    /// The BEP 15 schedule: 15 seconds, doubling up to 8 times
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(15),
+            max_retries: 8,
+        }
+    }
+}
+
+/// Announce over UDP, returning the parsed response, the raw reply packet
+/// and the sizes of both packets
+pub(super) fn announce(
+    tracker: &Tracker,
+    request: &TrackerRequest,
+) -> crate::Result<(TrackerResponse, Vec<u8>, AnnounceStats)> {
+    let mut exchange = Exchange::open(&tracker.announce_url, tracker.udp_timeouts)?;
+    announce_over(&mut exchange, tracker, request)
+}
+
+fn announce_over(
+    exchange: &mut Exchange,
+    tracker: &Tracker,
+    request: &TrackerRequest,
+) -> crate::Result<(TrackerResponse, Vec<u8>, AnnounceStats)> {
+    let transaction_id = rand::thread_rng().r#gen::<u32>();
+    // The connection id is filled in by `transact` before each send
+    let mut packet = announce_packet(0, transaction_id, request);
+    let reply = exchange.transact(&mut packet, ACTION_ANNOUNCE, transaction_id)?;
+
+    // action, transaction id, interval, leechers, seeders, then peers
+    if reply.len() < 20 {
+        return Err(crate::Error::Tracker(
+            "UDP announce response too short".to_string(),
+        ));
+    }
+    let response = TrackerResponse {
+        interval: read_u32(&reply, 8),
+        min_interval: None,
+        peers: Tracker::parse_compact_peers(&reply[20..], tracker.strict_peers)?,
+        peer_hosts: Vec::new(),
+        seeders: Some(read_u32(&reply, 16)),
+        leechers: Some(read_u32(&reply, 12)),
+        warning: None,
+        tracker_id: None,
+    };
+
+    tracing::info!("Received {} peers from tracker", response.peers.len());
+
+    let stats = AnnounceStats {
+        request_url_len: packet.len(),
+        response_body_len: reply.len(),
+    };
+    stats.log();
+    Ok((response, reply, stats))
+}
+
+/// Check that a UDP tracker answers a connect request within `timeout`
+pub(super) fn is_alive(announce_url: &str, timeout: Duration) -> bool {
+    let timeouts = UdpTimeouts {
+        base: timeout,
+        max_retries: 0,
+    };
+    match Exchange::open(announce_url, timeouts).and_then(|mut e| e.connect()) {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!("Tracker {} is unreachable: {}", announce_url, e);
+            false
+        }
+    }
+}
+
+/// One conversation with a tracker, sharing the retransmission count
+struct Exchange {
+    socket: UdpSocket,
+    timeouts: UdpTimeouts,
+    retries: u32,
+    /// Current connection id and when it was obtained
+    connection: Option<(u64, Instant)>,
+    connection_ttl: Duration,
+}
+
+impl Exchange {
+    fn open(announce_url: &str, timeouts: UdpTimeouts) -> crate::Result<Self> {
+        let url = Url::parse(announce_url)?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| crate::Error::Tracker(format!("No host in {}", announce_url)))?;
+        let port = url
+            .port()
+            .ok_or_else(|| crate::Error::Tracker(format!("No port in {}", announce_url)))?;
+        // Bracketed IPv6 hosts must be bare for resolution
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addr = (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| crate::Error::Tracker(format!("Could not resolve {}", announce_url)))?;
+
+        let local: SocketAddr = if addr.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+
+        Ok(Self {
+            socket,
+            timeouts,
+            retries: 0,
+            connection: None,
+            connection_ttl: CONNECTION_ID_TTL,
+        })
+    }
+
+    fn connect(&mut self) -> crate::Result<u64> {
+        let transaction_id = rand::thread_rng().r#gen::<u32>();
+        let mut packet = Vec::with_capacity(16);
+        packet.extend_from_slice(&PROTOCOL_ID.to_be_bytes());
+        packet.extend_from_slice(&ACTION_CONNECT.to_be_bytes());
+        packet.extend_from_slice(&transaction_id.to_be_bytes());
+
+        let reply = self.transact(&mut packet, ACTION_CONNECT, transaction_id)?;
+        if reply.len() < 16 {
+            return Err(crate::Error::Tracker(
+                "UDP connect response too short".to_string(),
+            ));
+        }
+        let connection_id = u64::from_be_bytes(reply[8..16].try_into().unwrap());
+        self.connection = Some((connection_id, Instant::now()));
+        Ok(connection_id)
+    }
+
+    /// The current connection id, connecting first if there is none or it
+    /// has expired
+    fn connection_id(&mut self) -> crate::Result<u64> {
+        match self.connection {
+            Some((id, obtained)) if obtained.elapsed() < self.connection_ttl => Ok(id),
+            _ => self.connect(),
+        }
+    }
+
+    /// Wait for a reply before the next retransmission
+    fn timeout(&self) -> Duration {
+        self.timeouts
+            .base
+            .saturating_mul(1 << self.retries.min(MAX_BACKOFF_EXPONENT))
+    }
+
+    /// Send `packet` until a reply to `transaction_id` arrives
+    ///
+    /// Requests other than connect start with the connection id, which is
+    /// written in before every send so a retransmission never carries an
+    /// expired one. Replies for other transactions are ignored. An error
+    /// reply from the tracker becomes `Error::Tracker` with its message.
+    fn transact(
+        &mut self,
+        packet: &mut [u8],
+        action: u32,
+        transaction_id: u32,
+    ) -> crate::Result<Vec<u8>> {
+        let mut buf = vec![0u8; 2048];
+        loop {
+            if action != ACTION_CONNECT {
+                let connection_id = self.connection_id()?;
+                packet[..8].copy_from_slice(&connection_id.to_be_bytes());
+            }
+            self.socket.send(packet)?;
+            let deadline = Instant::now() + self.timeout();
+
+            while let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                if wait.is_zero() {
+                    break;
+                }
+                self.socket.set_read_timeout(Some(wait))?;
+                let len = match self.socket.recv(&mut buf) {
+                    Ok(len) => len,
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        break;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let reply = &buf[..len];
+                if len < 8 || read_u32(reply, 4) != transaction_id {
+                    continue;
+                }
+                match read_u32(reply, 0) {
+                    a if a == action => return Ok(reply.to_vec()),
+                    ACTION_ERROR => {
+                        return Err(crate::Error::Tracker(
+                            String::from_utf8_lossy(&reply[8..]).into_owned(),
+                        ));
+                    }
+                    _ => continue,
+                }
+            }
+
+            if self.retries >= self.timeouts.max_retries {
+                return Err(crate::Error::Tracker(
+                    "UDP tracker did not respond".to_string(),
+                ));
+            }
+            self.retries += 1;
+            tracing::debug!(
+                "Retransmitting UDP tracker request (attempt {})",
+                self.retries
+            );
+        }
+    }
+}
+
+fn announce_packet(connection_id: u64, transaction_id: u32, request: &TrackerRequest) -> Vec<u8> {
+    let event: u32 = match request.event {
+        None => 0,
+        Some(TrackerEvent::Completed) => 1,
+        Some(TrackerEvent::Started) => 2,
+        Some(TrackerEvent::Stopped) => 3,
+    };
+
+    let mut packet = Vec::with_capacity(98);
+    packet.extend_from_slice(&connection_id.to_be_bytes());
+    packet.extend_from_slice(&ACTION_ANNOUNCE.to_be_bytes());
+    packet.extend_from_slice(&transaction_id.to_be_bytes());
+    packet.extend_from_slice(&request.info_hash);
+    packet.extend_from_slice(&request.peer_id);
+    packet.extend_from_slice(&request.downloaded.to_be_bytes());
+    packet.extend_from_slice(&request.left.to_be_bytes());
+    packet.extend_from_slice(&request.uploaded.to_be_bytes());
+    packet.extend_from_slice(&event.to_be_bytes());
+    // IP address: 0 lets the tracker use the packet's source address
+    let ip = request.ipv4.map_or(0, u32::from);
+    packet.extend_from_slice(&ip.to_be_bytes());
+    packet.extend_from_slice(&key_number(request.key.as_deref()).to_be_bytes());
+    // num_want: -1 lets the tracker decide
+    let num_want = request
+        .numwant
+        .map_or(-1, |n| i32::try_from(n).unwrap_or(i32::MAX));
+    packet.extend_from_slice(&num_want.to_be_bytes());
+    packet.extend_from_slice(&request.port.to_be_bytes());
+    packet
+}
+
+/// The 32-bit `key` field, from the hex key HTTP announces send
+///
+/// Keys that aren't hex are hashed so they still stay stable.
+fn key_number(key: Option<&str>) -> u32 {
+    match key {
+        None => 0,
+        Some(key) => u32::from_str_radix(key, 16).unwrap_or_else(|_| {
+            u32::from_be_bytes(Sha1::digest(key.as_bytes())[..4].try_into().unwrap())
+        }),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Serve one connect and one announce, ignoring the first `drop` packets
+    fn mock_udp_tracker(drop: usize) -> (String, thread::JoinHandle<Vec<u8>>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}/announce", socket.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            for _ in 0..drop {
+                socket.recv_from(&mut buf).unwrap();
+            }
+
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(len, 16);
+            assert_eq!(
+                u64::from_be_bytes(buf[..8].try_into().unwrap()),
+                PROTOCOL_ID
+            );
+            let mut reply = vec![0, 0, 0, 0];
+            reply.extend_from_slice(&buf[12..16]);
+            reply.extend_from_slice(&0xC0FFEEu64.to_be_bytes());
+            socket.send_to(&reply, from).unwrap();
+
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            let announce = buf[..len].to_vec();
+            let mut reply = vec![0, 0, 0, 1];
+            reply.extend_from_slice(&announce[12..16]);
+            reply.extend_from_slice(&1800u32.to_be_bytes());
+            reply.extend_from_slice(&3u32.to_be_bytes());
+            reply.extend_from_slice(&5u32.to_be_bytes());
+            reply.extend_from_slice(&[10, 0, 0, 1, 0x1A, 0xE1]);
+            socket.send_to(&reply, from).unwrap();
+            announce
+        });
+
+        (url, handle)
+    }
+
+    fn fast_tracker(url: String) -> Tracker {
+        Tracker::new(url).with_udp_timeouts(UdpTimeouts {
+            base: Duration::from_millis(100),
+            max_retries: 3,
+        })
+    }
+
+    #[test]
+    fn test_udp_announce() {
+        let (url, server) = mock_udp_tracker(0);
+        let mut request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+        request.key = Some("0000BEEF".to_string());
+
+        let response = fast_tracker(url).announce(&request).unwrap();
+        let packet = server.join().unwrap();
+
+        assert_eq!(response.interval, 1800);
+        assert_eq!(response.leechers, Some(3));
+        assert_eq!(response.seeders, Some(5));
+        assert_eq!(response.peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+
+        assert_eq!(packet.len(), 98);
+        assert_eq!(
+            u64::from_be_bytes(packet[..8].try_into().unwrap()),
+            0xC0FFEE
+        );
+        assert_eq!(&packet[16..36], &[1u8; 20]);
+        assert_eq!(read_u32(&packet, 80), 2); // started
+        assert_eq!(read_u32(&packet, 88), 0xBEEF);
+        assert_eq!(&packet[96..98], &6881u16.to_be_bytes());
+    }
+
+    #[test]
+    fn test_udp_announce_stats() {
+        let (url, server) = mock_udp_tracker(0);
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        let (_, stats) = fast_tracker(url).announce_with_stats(&request).unwrap();
+        server.join().unwrap();
+
+        // The packets actually exchanged, not the length of the udp:// URL
+        assert_eq!(
+            stats,
+            AnnounceStats {
+                request_url_len: 98,
+                response_body_len: 26,
+            }
+        );
+    }
+
+    #[test]
+    fn test_udp_retransmits_lost_packets() {
+        let (url, server) = mock_udp_tracker(2);
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        let response = fast_tracker(url).announce(&request).unwrap();
+        server.join().unwrap();
+        assert_eq!(response.interval, 1800);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}", socket.local_addr().unwrap());
+        let mut exchange = Exchange::open(&url, UdpTimeouts::default()).unwrap();
+
+        assert_eq!(exchange.timeout(), Duration::from_secs(15));
+        exchange.retries = 3;
+        assert_eq!(exchange.timeout(), Duration::from_secs(120));
+        exchange.retries = 8;
+        assert_eq!(exchange.timeout(), Duration::from_secs(15 * 256));
+        exchange.retries = 40;
+        assert_eq!(exchange.timeout(), Duration::from_secs(15 * 256));
+
+        exchange.timeouts.base = Duration::MAX;
+        assert_eq!(exchange.timeout(), Duration::MAX);
+    }
+
+    #[test]
+    fn test_udp_reconnects_after_connection_id_expires() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}/announce", socket.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            let mut announced_with = Vec::new();
+            for connection_id in [1u64, 2] {
+                let (len, from) = socket.recv_from(&mut buf).unwrap();
+                assert_eq!(len, 16, "expected a connect request");
+                let mut reply = vec![0, 0, 0, 0];
+                reply.extend_from_slice(&buf[12..16]);
+                reply.extend_from_slice(&connection_id.to_be_bytes());
+                socket.send_to(&reply, from).unwrap();
+
+                let (len, from) = socket.recv_from(&mut buf).unwrap();
+                assert_eq!(len, 98, "expected an announce");
+                announced_with.push(u64::from_be_bytes(buf[..8].try_into().unwrap()));
+                if connection_id == 2 {
+                    // Only answer once the id was refreshed
+                    let mut reply = vec![0, 0, 0, 1];
+                    reply.extend_from_slice(&buf[12..16]);
+                    reply.extend_from_slice(&[0; 12]);
+                    socket.send_to(&reply, from).unwrap();
+                }
+            }
+            announced_with
+        });
+
+        let tracker = fast_tracker(url);
+        let mut exchange = Exchange::open(&tracker.announce_url, tracker.udp_timeouts).unwrap();
+        exchange.connection_ttl = Duration::from_millis(50);
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+
+        announce_over(&mut exchange, &tracker, &request).unwrap();
+        assert_eq!(server.join().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_udp_error_and_timeout() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}", socket.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            let (_, from) = socket.recv_from(&mut buf).unwrap();
+            let mut reply = vec![0, 0, 0, 3];
+            reply.extend_from_slice(&buf[12..16]);
+            reply.extend_from_slice(b"torrent not registered");
+            socket.send_to(&reply, from).unwrap();
+        });
+
+        let request = TrackerRequest::new_started([1u8; 20], [2u8; 20], 6881, 1000);
+        match fast_tracker(url).announce(&request) {
+            Err(crate::Error::Tracker(message)) => assert_eq!(message, "torrent not registered"),
+            other => panic!("Expected a tracker error, got {:?}", other),
+        }
+        server.join().unwrap();
+
+        // Nothing answers on this socket
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let url = format!("udp://{}", silent.local_addr().unwrap());
+        let tracker = Tracker::new(url).with_udp_timeouts(UdpTimeouts {
+            base: Duration::from_millis(20),
+            max_retries: 2,
+        });
+        assert!(matches!(
+            tracker.announce(&request),
+            Err(crate::Error::Tracker(_))
+        ));
+    }
+}
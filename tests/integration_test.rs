@@ -16,7 +16,7 @@ fn create_test_torrent_file(dir: &TempDir) -> std::path::PathBuf {
 
 fn create_minimal_torrent() -> Vec<u8> {
     // A valid minimal .torrent file in bencode format
-    let data = "d8:announce23:http://tracker.test:6969\
+    let data = "d8:announce24:http://tracker.test:6969\
                  4:infod6:lengthi1048576e\
                  4:name9:test.file\
                  12:piece lengthi262144e\
@@ -59,7 +59,7 @@ fn test_info_hash_deterministic() {
 #[test]
 fn test_multi_file_torrent() {
     // Create a multi-file torrent
-    let torrent_data = "d8:announce23:http://tracker.test:6969\
+    let torrent_data = "d8:announce24:http://tracker.test:6969\
                         4:infod5:filesl\
                         d6:lengthi1000e4:pathl5:file1ee\
                         d6:lengthi2000e4:pathl5:file2ee\